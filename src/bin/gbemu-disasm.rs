@@ -0,0 +1,169 @@
+// Dumps one ROM bank as SM83 assembly
+
+use std::path::Path;
+
+use gbemu::comps::{common::parse_hex, disasm::disassemble, symbols::SymbolsContext};
+
+const USAGE: &str = "\
+Usage: gbemu-disasm <ROM> [BANK]
+
+Disassembles ROM bank BANK (hexadecimal, default: 0). Bank 0 is mapped at $0000, every other bank at $4000.
+Labels from an RGBDS .sym file next to the ROM are included.
+";
+
+const BANK_SIZE: usize = 0x4000;
+
+// Entry points in bank 0
+const LABELS: [(u16, &str); 14] = [
+    (0x00, "RST_00"),
+    (0x08, "RST_08"),
+    (0x10, "RST_10"),
+    (0x18, "RST_18"),
+    (0x20, "RST_20"),
+    (0x28, "RST_28"),
+    (0x30, "RST_30"),
+    (0x38, "RST_38"),
+    (0x40, "VBlank"),
+    (0x48, "STAT"),
+    (0x50, "Timer"),
+    (0x58, "Serial"),
+    (0x60, "Joypad"),
+    (0x100, "Entry"),
+];
+
+// Cartridge header fields from $0104 to $014F, shown as data instead of code
+const HEADER: [(u16, u16, &str); 14] = [
+    (0x104, 0x133, "Logo"),
+    (0x134, 0x142, "Title"),
+    (0x143, 0x143, "CGB flag"),
+    (0x144, 0x145, "New licensee code"),
+    (0x146, 0x146, "SGB flag"),
+    (0x147, 0x147, "Cartridge type"),
+    (0x148, 0x148, "ROM size"),
+    (0x149, 0x149, "RAM size"),
+    (0x14A, 0x14A, "Destination code"),
+    (0x14B, 0x14B, "Old licensee code"),
+    (0x14C, 0x14C, "Version"),
+    (0x14D, 0x14D, "Header checksum"),
+    (0x14E, 0x14F, "Global checksum"),
+    (0x150, 0x150, ""), // End of the header
+];
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    let (rom, bank) = match args.as_slice() {
+        [_, rom] => (rom, 0),
+        [_, rom, bank] => match parse_hex(bank) {
+            Some(bank) => (rom, bank as usize),
+            None => {
+                eprintln!("Invalid bank: {bank}\n\n{USAGE}");
+                std::process::exit(2);
+            }
+        },
+        _ => {
+            eprint!("{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    let data = match std::fs::read(rom) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("Failed to read {rom}: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let start = bank * BANK_SIZE;
+    if start >= data.len() {
+        eprintln!("{rom} has no bank {bank:X} ({} banks)", data.len().div_ceil(BANK_SIZE));
+        std::process::exit(1);
+    }
+
+    let bytes = &data[start..(start + BANK_SIZE).min(data.len())];
+    let base = if bank == 0 { 0x0000 } else { 0x4000 };
+
+    let mut labels = Vec::new();
+    if bank == 0 {
+        labels.extend(LABELS.iter().map(|(address, name)| (*address, name.to_string())));
+    }
+
+    let sym = Path::new(rom).with_extension("sym");
+    if sym.exists() {
+        let mut symbols = SymbolsContext { list: Vec::new() };
+        if let Err(e) = symbols.load(&sym.to_string_lossy()) {
+            eprintln!("Couldn't load symbols from {}: {e}", sym.display());
+            std::process::exit(1);
+        }
+
+        // The .sym file also lists RAM labels, keep the ones inside this bank
+        let end = base as usize + bytes.len();
+        labels.extend(
+            symbols.list.into_iter()
+                .filter(|symbol| symbol.bank as usize == bank && (base as usize..end).contains(&(symbol.address as usize)))
+                .map(|symbol| (symbol.address, symbol.name)),
+        );
+    }
+
+    print!("{}", disassemble_bank(bytes, bank, &labels));
+}
+
+fn disassemble_bank(bytes: &[u8], bank: usize, labels: &[(u16, String)]) -> String {
+    let bank0 = bank == 0;
+    let base = if bank0 { 0x0000 } else { 0x4000 };
+    let mut out = format!("; Bank {bank:X}\n");
+    let mut offset = 0;
+
+    // Labels and header fields split the code, so an instruction never runs into one
+    let mut boundaries = labels.iter().map(|(address, _)| (*address - base) as usize).collect::<Vec<usize>>();
+    if bank0 {
+        boundaries.extend(HEADER.iter().map(|(start, _, _)| *start as usize));
+    }
+
+    while offset < bytes.len() {
+        let address = base.wrapping_add(offset as u16);
+
+        for (_, name) in labels.iter().filter(|(other, _)| *other == address) {
+            out.push_str(&format!("\n{name}:\n"));
+        }
+
+        if bank0 {
+            if let Some((start, end, name)) = HEADER.iter().find(|(start, _, name)| *start == address && !name.is_empty()) {
+                if *start == 0x104 {
+                    out.push_str("\nHeader:\n");
+                }
+
+                let end = (*end as usize + 1).min(bytes.len());
+                out.push_str(&data_line(address, &bytes[offset..end], name));
+                offset = end;
+                continue;
+            }
+        }
+
+        let limit = boundaries.iter().copied().filter(|other| *other > offset).min().unwrap_or(bytes.len()).min(bytes.len());
+        let (text, len) = disassemble(&bytes[offset..limit], address);
+
+        let hex = bytes[offset..offset + len].iter().map(|byte| format!("{byte:02X}")).collect::<Vec<String>>().join(" ");
+        out.push_str(&format!("    ${address:04X}: {hex:8}  {text}\n"));
+        offset += len;
+    }
+
+    out
+}
+
+fn data_line(address: u16, bytes: &[u8], name: &str) -> String {
+    let values = bytes.iter().map(|byte| format!("${byte:02X}")).collect::<Vec<String>>();
+    let mut lines = if name == "Title" {
+        let title = bytes.iter().take_while(|byte| **byte != 0).map(|byte| *byte as char).collect::<String>();
+        format!("    ; {name}: \"{title}\"\n")
+    } else {
+        format!("    ; {name}\n")
+    };
+
+    for (i, chunk) in values.chunks(16).enumerate() {
+        lines.push_str(&format!("    ${:04X}: DB {}\n", address as usize + i * 16, chunk.join(",")));
+    }
+
+    lines
+}
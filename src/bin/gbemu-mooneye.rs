@@ -0,0 +1,102 @@
+// Runs every Mooneye test ROM in a directory and prints a pass/fail table
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+const USAGE: &str = "\
+Usage: gbemu-mooneye <DIR> [GBEMU OPTIONS]
+
+Runs every .gb file in DIR and its subdirectories with gbemu --headless --mooneye, one process per ROM.
+Options after DIR are passed on to gbemu, e.g. --model or --frames (default: --frames 1200).
+Exits with 1 if any test didn't pass.
+";
+
+const DEFAULT_FRAMES: &str = "1200"; // 20 seconds, the slowest tests finish well within that
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    let (dir, options) = match args.as_slice() {
+        [_, dir, options @ ..] if !dir.starts_with('-') => (dir, options),
+        _ => {
+            eprint!("{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    let mut roms = Vec::new();
+    if let Err(e) = find_roms(Path::new(dir), &mut roms) {
+        eprintln!("Couldn't read {dir}: {e}");
+        std::process::exit(1);
+    }
+
+    if roms.is_empty() {
+        eprintln!("No .gb files in {dir}");
+        std::process::exit(1);
+    }
+
+    roms.sort();
+
+    // gbemu is built next to this binary
+    let gbemu = std::env::current_exe().unwrap().with_file_name(format!("gbemu{}", std::env::consts::EXE_SUFFIX));
+
+    let names = roms.iter().map(|rom| rom.strip_prefix(dir).unwrap_or(rom).display().to_string()).collect::<Vec<String>>();
+    let width = names.iter().map(|name| name.len()).max().unwrap();
+    let mut passed = 0;
+
+    for (rom, name) in roms.iter().zip(&names) {
+        let output = Command::new(&gbemu)
+            .args(["--headless", "--speed", "0", "--mooneye", "--frames", DEFAULT_FRAMES])
+            .args(options)
+            .arg(rom)
+            .output();
+
+        let (result, details) = match output {
+            Ok(output) => {
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                let last_line = stdout.lines().last().unwrap_or("").to_string();
+
+                match output.status.code() {
+                    Some(0) => ("PASS", String::new()),
+                    Some(1) if last_line.starts_with("Mooneye test failed: ") => ("FAIL", last_line.trim_start_matches("Mooneye test failed: ").to_string()),
+                    Some(1) if last_line.starts_with("Mooneye test didn't finish") => ("TIMEOUT", String::new()),
+                    _ => {
+                        let stderr = String::from_utf8_lossy(&output.stderr);
+                        ("ERROR", stderr.lines().find(|line| !line.is_empty()).unwrap_or("").to_string())
+                    }
+                }
+            }
+            Err(e) => ("ERROR", format!("Couldn't run {}: {e}", gbemu.display())),
+        };
+
+        if result == "PASS" {
+            passed += 1;
+        }
+
+        let line = format!("{name:width$}  {result:7}  {details}");
+        println!("{}", line.trim_end());
+    }
+
+    println!("\n{passed}/{} passed", roms.len());
+
+    if passed != roms.len() {
+        std::process::exit(1);
+    }
+}
+
+fn find_roms(dir: &Path, roms: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            find_roms(&path, roms)?;
+        } else if path.extension().is_some_and(|extension| extension == "gb") {
+            roms.push(path);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,209 @@
+use std::str::FromStr;
+
+use gbemu::comps::{log::{parse_log_spec, Category, Level}, model::Model, pacing::{MAX_SPEED, MIN_SPEED}, trace::TraceFormat};
+
+pub const USAGE: &str = "\
+Usage: gbemu [OPTIONS] <ROM>
+
+Options:
+    --scale <N>                 Window scale factor (default: 2)
+    --headless                  Run without opening any windows
+    --frames <N>                Quit after N frames
+    --boot-rom <FILE>           Run the given boot ROM before the cartridge
+    --model <MODEL>             Hardware model: dmg0, dmg, mgb, sgb, sgb2 or cgb (default: dmg)
+    --no-debug-window           Don't open the tile viewer window
+    --speed <X>                 Emulation speed multiplier from 0.125 to 8, 0 for uncapped (default: 1)
+    --palette <PALETTE>         grey, green, pocket or four hex colors (e.g. FFFFFF,AAAAAA,555555,000000)
+    --trace <FILE>              Write an instruction trace to FILE
+    --trace-format <FORMAT>     Trace format, full or doctor (Gameboy-Doctor) (default: full)
+    --stub-ly                   Always read LY as 0x90, needed to match Gameboy-Doctor logs
+    --serial-out <FILE>         Write bytes sent over the serial port to FILE
+    --link-listen <PORT>        Wait for another gbemu to connect a link cable on localhost:PORT
+    --link-connect <HOST:PORT>  Connect a link cable to another gbemu started with --link-listen
+    --log <SPEC>                Log levels on stderr, LEVEL or CATEGORY=LEVEL separated by commas (default: info)
+                                Categories: cart, bus, ppu, cpu, serial, timing, ui
+                                Levels: off, error, warn, info, debug (e.g. --log warn,bus=debug)
+    --screenshot-at <FRAME> <PATH>
+                                Save frame number FRAME as a PNG to PATH
+    --screenshot <PATH>         Save the last frame as a PNG to PATH when the emulator stops
+    --until-ld-b-b              Stop at the end of the frame in which LD B,B is first executed
+    --rewind-frames <N>         Number of frames kept for rewinding, 0 to disable (default: 600)
+    --rewind-budget <MB>        Memory limit of the rewind history (default: 64)
+    --debug                     Start stopped in the command-line debugger (type help for commands)
+    --gdb <PORT>                Start stopped and wait for a GDB remote protocol client on localhost:PORT
+    --mooneye                   Stop after the first LD B,B and exit with the result of a Mooneye test ROM
+    -h, --help                  Print this help message
+
+Symbols from an RGBDS .sym file next to the ROM (<ROM>.sym) are shown in the debugger and traces.
+
+Keys:
+    Escape                      Quit
+    F10                         Toggle integer / aspect-correct scaling
+    F11                         Toggle fullscreen
+    F5 / F9                     Save / load the state in the current slot (stored as <ROM>.ss0 - <ROM>.ss9)
+    F6 / F7                     Previous / next save state slot
+    Backspace                   Rewind one frame (hold to keep rewinding)
+    P                           Pause / resume
+    N                           Advance one frame (pauses first)
+    Tab                         Fast-forward, uncapped while held
+    - / =                       Halve / double the speed (1/8x to 8x)
+";
+
+pub struct Options {
+    pub rom: String,
+    pub scale: u32,
+    pub headless: bool,
+    pub frames: Option<u32>,
+    pub boot_rom: Option<String>,
+    pub model: Model,
+    pub debug_window: bool,
+    pub speed: f32,
+    pub palette: Option<[u32; 4]>,
+    pub trace: Option<String>,
+    pub trace_format: TraceFormat,
+    pub stub_ly: bool,
+    pub serial_out: Option<String>,
+    pub link_listen: Option<u16>,
+    pub link_connect: Option<String>,
+    pub log: Vec<(Option<Category>, Level)>,
+    pub screenshot: Option<(u32, String)>,
+    pub exit_screenshot: Option<String>,
+    pub until_ld_b_b: bool,
+    pub rewind_frames: usize,
+    pub rewind_budget: usize,
+    pub debug: bool,
+    pub gdb: Option<u16>,
+    pub mooneye: bool,
+}
+
+pub enum Command {
+    Run(Box<Options>),
+    Help,
+}
+
+pub fn parse(args: &[String]) -> Result<Command, String> {
+    let mut rom = None;
+    let mut options = Options {
+        rom: String::new(),
+        scale: 2,
+        headless: false,
+        frames: None,
+        boot_rom: None,
+        model: Model::DMG,
+        debug_window: true,
+        speed: 1.0,
+        palette: None,
+        trace: None,
+        trace_format: TraceFormat::Full,
+        stub_ly: false,
+        serial_out: None,
+        link_listen: None,
+        link_connect: None,
+        log: Vec::new(),
+        screenshot: None,
+        exit_screenshot: None,
+        until_ld_b_b: false,
+        rewind_frames: 600,
+        rewind_budget: 64,
+        debug: false,
+        gdb: None,
+        mooneye: false,
+    };
+
+    let mut args = args.iter().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--scale" => options.scale = number(arg, args.next())?,
+            "--headless" => options.headless = true,
+            "--frames" => options.frames = Some(number(arg, args.next())?),
+            "--boot-rom" => options.boot_rom = Some(value(arg, args.next())?.clone()),
+            "--model" => options.model = value(arg, args.next())?.parse()?,
+            "--no-debug-window" => options.debug_window = false,
+            "--speed" => options.speed = number(arg, args.next())?,
+            "--palette" => options.palette = Some(palette(value(arg, args.next())?)?),
+            "--trace" => options.trace = Some(value(arg, args.next())?.clone()),
+            "--trace-format" => options.trace_format = value(arg, args.next())?.parse()?,
+            "--stub-ly" => options.stub_ly = true,
+            "--serial-out" => options.serial_out = Some(value(arg, args.next())?.clone()),
+            "--link-listen" => options.link_listen = Some(number(arg, args.next())?),
+            "--link-connect" => options.link_connect = Some(value(arg, args.next())?.clone()),
+            "--log" => options.log.extend(parse_log_spec(value(arg, args.next())?)?),
+            "--screenshot-at" => {
+                let frame = number(arg, args.next())?;
+                let path = value(arg, args.next())?.clone();
+                options.screenshot = Some((frame, path));
+            }
+            "--screenshot" => options.exit_screenshot = Some(value(arg, args.next())?.clone()),
+            "--until-ld-b-b" => options.until_ld_b_b = true,
+            "--rewind-frames" => options.rewind_frames = number(arg, args.next())?,
+            "--rewind-budget" => options.rewind_budget = number(arg, args.next())?,
+            "--debug" => options.debug = true,
+            "--gdb" => options.gdb = Some(number(arg, args.next())?),
+            "--mooneye" => options.mooneye = true,
+            flag if flag.starts_with('-') => return Err(format!("Unknown option: {flag}")),
+            path => {
+                if rom.is_some() {
+                    return Err(format!("Unexpected argument: {path}"));
+                }
+
+                rom = Some(path.to_string());
+            }
+        }
+    }
+
+    if options.scale == 0 {
+        return Err(String::from("--scale must be at least 1"));
+    }
+
+    // NOTICE: Also catches NaN, which fails every comparison
+    if options.speed != 0.0 && !(MIN_SPEED..=MAX_SPEED).contains(&options.speed) {
+        return Err(format!("--speed must be 0 (uncapped) or between {MIN_SPEED} and {MAX_SPEED}"));
+    }
+
+    if options.link_listen.is_some() && options.link_connect.is_some() {
+        return Err(String::from("--link-listen and --link-connect can't be used together"));
+    }
+
+    match rom {
+        Some(rom) => options.rom = rom,
+        None => return Err(String::from("No ROM file given")),
+    }
+
+    Ok(Command::Run(Box::new(options)))
+}
+
+fn value<'a>(flag: &str, value: Option<&'a String>) -> Result<&'a String, String> {
+    value.ok_or(format!("{flag} expects a value"))
+}
+
+fn number<T: FromStr>(flag: &str, arg: Option<&String>) -> Result<T, String> {
+    let arg = value(flag, arg)?;
+    arg.parse().map_err(|_| format!("Invalid value for {flag}: {arg}"))
+}
+
+// Colors are given as RRGGBB, from lightest to darkest, and stored as ABGR8888 like COLORS
+fn palette(arg: &str) -> Result<[u32; 4], String> {
+    let colors = match arg {
+        "grey" | "gray" => "FFFFFF,AAAAAA,555555,000000",
+        "green" => "9BBC0F,8BAC0F,306230,0F380F",
+        "pocket" => "C4CFA1,8B956D,4D533C,1F1F1F",
+        colors => colors,
+    };
+
+    let colors = colors
+        .split(',')
+        .map(|color| match u32::from_str_radix(color, 16) {
+            Ok(rgb) if color.len() == 6 => {
+                let r = (rgb >> 16) & 0xFF;
+                let g = (rgb >> 8) & 0xFF;
+                let b = rgb & 0xFF;
+                Ok(0xFF000000 | (b << 16) | (g << 8) | r)
+            }
+            _ => Err(format!("Invalid palette color: {color}")),
+        })
+        .collect::<Result<Vec<u32>, String>>()?;
+
+    colors.try_into().map_err(|_| String::from("A palette needs exactly four colors"))
+}
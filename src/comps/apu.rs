@@ -0,0 +1,22 @@
+// Audio Processing Unit
+// NOTICE: Only the register values are stored (0xFF10 - 0xFF3F), no sound is generated yet
+
+use std::sync::RwLock;
+
+pub struct APUContext {
+    pub registers: [u8; 0x30],
+}
+
+pub static APU: RwLock<APUContext> = RwLock::new(APUContext {
+    registers: [0; 0x30],
+});
+
+impl APUContext {
+    pub fn read(&self, address: u16) -> u8 {
+        self.registers[(address - 0xFF10) as usize]
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        self.registers[(address - 0xFF10) as usize] = value;
+    }
+}
@@ -0,0 +1,75 @@
+// Boot ROM, mapped over the start of the cartridge until 0xFF50 is written
+
+use std::{fs::File, io::{self, Read}, sync::RwLock};
+
+use super::{cpu::CPUContext, lcd::LCD, timer::TIMER};
+
+const DMG_BOOT_SIZE: usize = 0x100;
+const CGB_BOOT_SIZE: usize = 0x900; // 0x0000 - 0x00FF and 0x0200 - 0x08FF, the header shows through in between
+
+pub struct BootContext {
+    pub rom: Vec<u8>,
+    pub mapped: bool,
+}
+
+pub static BOOT: RwLock<BootContext> = RwLock::new(BootContext {
+    rom: vec![],
+    mapped: false,
+});
+
+impl BootContext {
+    pub fn load(&mut self, filename: &str) -> io::Result<()> {
+        let mut file = File::open(filename)?;
+        self.rom.clear();
+        file.read_to_end(&mut self.rom)?;
+
+        if self.rom.len() != DMG_BOOT_SIZE && self.rom.len() != CGB_BOOT_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Expected a {DMG_BOOT_SIZE} or {CGB_BOOT_SIZE} byte boot ROM, got {} bytes", self.rom.len()),
+            ));
+        }
+
+        self.mapped = true;
+
+        Ok(())
+    }
+
+    pub fn maps(&self, address: u16) -> bool {
+        self.mapped && (address < 0x100 || (0x200 <= address && (address as usize) < self.rom.len()))
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        self.rom[address as usize]
+    }
+
+    // Writing a non-zero value to 0xFF50 unmaps the boot ROM until the next reset, writing 0 does nothing
+    pub fn write(&mut self, value: u8) {
+        if value != 0 {
+            self.mapped = false;
+        }
+    }
+}
+
+// Puts the hardware in its power-on state, which the boot ROM expects instead of the post-boot state
+pub fn boot_power_on(cpu: &mut CPUContext) {
+    cpu.registers.a = 0;
+    cpu.registers.f = 0;
+    cpu.registers.b = 0;
+    cpu.registers.c = 0;
+    cpu.registers.d = 0;
+    cpu.registers.e = 0;
+    cpu.registers.h = 0;
+    cpu.registers.l = 0;
+    cpu.registers.sp = 0;
+    cpu.registers.pc = 0;
+
+    TIMER.write().unwrap().div = 0;
+
+    let mut lcd = LCD.write().unwrap();
+    lcd.control = 0;
+    lcd.bg_palette = 0;
+    lcd.obj1_palette = 0;
+    lcd.obj2_palette = 0;
+    lcd.refresh_palettes();
+}
@@ -0,0 +1,289 @@
+// Breakpoints, watchpoints and interrupt breakpoints for the debugger
+
+use std::sync::{atomic::{AtomicBool, Ordering}, RwLock};
+
+use super::{
+    cart::CART,
+    cpu::CPUContext,
+    debugger::number,
+    expr::{parse_expr, Expr},
+    interrupts::InterruptType,
+    ppu::PPUContext,
+    symbols::{parse_address, symbol_at, SYMBOL_TABLE},
+};
+
+pub enum BreakKind {
+    Pc { address: u16, bank: Option<u16> },
+    Watch { start: u16, end: u16, read: bool, write: bool },
+    Interrupt(Option<InterruptType>), // None breaks on any interrupt
+}
+
+type Condition = (String, Expr); // Source text and the parsed expression
+
+pub struct Breakpoint {
+    pub id: u32,
+    pub kind: BreakKind,
+    pub condition: Option<Condition>,
+}
+
+pub struct BreakpointsContext {
+    pub list: Vec<Breakpoint>,
+    pub next_id: u32,
+    pub hit: Option<String>, // Set when a watchpoint or interrupt breakpoint triggers during a step
+}
+
+pub static BREAKPOINTS: RwLock<BreakpointsContext> = RwLock::new(BreakpointsContext {
+    list: Vec::new(),
+    next_id: 1,
+    hit: None,
+});
+
+// Checked on every bus access, so they are kept outside of the lock
+static WATCHING: AtomicBool = AtomicBool::new(false);
+static SUPPRESSED: AtomicBool = AtomicBool::new(false);
+
+const INTERRUPT_NAMES: [(&str, InterruptType); 5] = [
+    ("vblank", InterruptType::VBlank),
+    ("stat", InterruptType::LCDStat),
+    ("timer", InterruptType::Timer),
+    ("serial", InterruptType::Serial),
+    ("joypad", InterruptType::Joypad),
+];
+
+fn interrupt_name(it: InterruptType) -> &'static str {
+    INTERRUPT_NAMES.iter().find(|(_, other)| *other as u8 == it as u8).unwrap().0
+}
+
+// Runs f without triggering watchpoints, for bus accesses made by the emulator itself rather than the program
+pub fn without_watchpoints<R>(f: impl FnOnce() -> R) -> R {
+    let suppressed = SUPPRESSED.swap(true, Ordering::Relaxed);
+    let result = f();
+    SUPPRESSED.store(suppressed, Ordering::Relaxed);
+
+    result
+}
+
+pub fn watching() -> bool {
+    WATCHING.load(Ordering::Relaxed) && !SUPPRESSED.load(Ordering::Relaxed)
+}
+
+fn symbol_suffix(symbol: Option<String>) -> String {
+    match symbol {
+        Some(symbol) => format!(" ({symbol})"),
+        None => String::new(),
+    }
+}
+
+impl Breakpoint {
+    fn condition_holds(&self, cpu: &CPUContext, ppu: &PPUContext) -> bool {
+        match &self.condition {
+            Some((_, expr)) => without_watchpoints(|| expr.eval(cpu, ppu) != 0),
+            None => true,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let kind = match &self.kind {
+            BreakKind::Pc { address, bank: Some(bank) } => {
+                let symbol = SYMBOL_TABLE.read().unwrap().lookup(*bank, *address);
+                format!("break ${bank:02X}:{address:04X}{}", symbol_suffix(symbol))
+            }
+            BreakKind::Pc { address, bank: None } => format!("break ${address:04X}{}", symbol_suffix(symbol_at(*address))),
+            BreakKind::Watch { start, end, read, write } => {
+                let access = match (read, write) {
+                    (true, false) => "r",
+                    (false, true) => "w",
+                    _ => "rw",
+                };
+
+                if start == end {
+                    format!("watch {access} ${start:04X}{}", symbol_suffix(symbol_at(*start)))
+                } else {
+                    format!("watch {access} ${start:04X}-${end:04X}{}", symbol_suffix(symbol_at(*start)))
+                }
+            }
+            BreakKind::Interrupt(Some(it)) => format!("break int {}", interrupt_name(*it)),
+            BreakKind::Interrupt(None) => String::from("break int"),
+        };
+
+        match &self.condition {
+            Some((source, _)) => format!("#{} {kind} if {source}", self.id),
+            None => format!("#{} {kind}", self.id),
+        }
+    }
+}
+
+impl BreakpointsContext {
+    pub fn add(&mut self, kind: BreakKind, condition: Option<Condition>) -> String {
+        let breakpoint = Breakpoint { id: self.next_id, kind, condition };
+        let description = breakpoint.describe();
+
+        self.next_id += 1;
+        self.list.push(breakpoint);
+        self.update_watching();
+
+        format!("Added {description}")
+    }
+
+    pub fn delete(&mut self, id: Option<u32>) -> Result<String, String> {
+        match id {
+            Some(id) => {
+                let index = self.list.iter().position(|b| b.id == id).ok_or(format!("No breakpoint #{id}"))?;
+                self.list.remove(index);
+            }
+            None => self.list.clear(),
+        }
+
+        self.update_watching();
+        Ok(String::new())
+    }
+
+    pub fn remove_where(&mut self, f: impl Fn(&Breakpoint) -> bool) {
+        self.list.retain(|b| !f(b));
+        self.update_watching();
+    }
+
+    fn update_watching(&self) {
+        let watching = self.list.iter().any(|b| matches!(b.kind, BreakKind::Watch { .. }));
+        WATCHING.store(watching, Ordering::Relaxed);
+    }
+
+    pub fn describe(&self) -> String {
+        if self.list.is_empty() {
+            return String::from("No breakpoints");
+        }
+
+        self.list.iter().map(|b| b.describe()).collect::<Vec<String>>().join("\n")
+    }
+}
+
+// Splits "<target> if <condition>" and parses the condition
+fn split_condition(args: &str) -> Result<(&str, Option<Condition>), String> {
+    match args.split_once(" if ") {
+        Some((target, condition)) => {
+            let condition = condition.trim();
+            Ok((target.trim(), Some((condition.to_string(), parse_expr(condition)?))))
+        }
+        None => Ok((args.trim(), None)),
+    }
+}
+
+// A label that only stops in its own bank when it is in switchable ROM, or an address. Labels come first like in
+// parse_address
+fn pc_target(arg: &str) -> Result<BreakKind, String> {
+    if let Some((bank, address)) = SYMBOL_TABLE.read().unwrap().resolve(arg) {
+        return Ok(BreakKind::Pc { address, bank: (0x4000..0x8000).contains(&address).then_some(bank) });
+    }
+
+    let address = number(arg).map_err(|_| format!("Invalid address or unknown label: {arg}"))?;
+    Ok(BreakKind::Pc { address, bank: None })
+}
+
+// break [BANK:]ADDR|LABEL [if COND] | break int [NAME] [if COND]
+pub fn break_command(args: &str) -> Result<String, String> {
+    let (target, condition) = split_condition(args)?;
+    let mut words = target.split_whitespace();
+
+    let kind = match words.next() {
+        Some("int") => match words.next() {
+            Some(name) => {
+                let (_, it) = INTERRUPT_NAMES.iter().find(|(other, _)| *other == name).ok_or(format!(
+                    "Unknown interrupt: {name} (expected one of vblank, stat, timer, serial, joypad)"
+                ))?;
+                BreakKind::Interrupt(Some(*it))
+            }
+            None => BreakKind::Interrupt(None),
+        },
+        Some(target) => match target.split_once(':') {
+            Some((bank, address)) => BreakKind::Pc { address: number(address)?, bank: Some(number(bank)?) },
+            None => pc_target(target)?,
+        },
+        None => return Err(String::from("break expects an address or int")),
+    };
+
+    Ok(BREAKPOINTS.write().unwrap().add(kind, condition))
+}
+
+// watch [r|w|rw] ADDR[-END]|LABEL [if COND]
+pub fn watch_command(args: &str) -> Result<String, String> {
+    let (target, condition) = split_condition(args)?;
+    let words = target.split_whitespace().collect::<Vec<&str>>();
+
+    let (read, write, range) = match words.as_slice() {
+        ["r", range] => (true, false, *range),
+        ["w", range] => (false, true, *range),
+        ["rw", range] | [range] => (true, true, *range),
+        _ => return Err(String::from("watch expects [r|w|rw] ADDR[-END]")),
+    };
+
+    let (start, end) = match range.split_once('-') {
+        Some((start, end)) => (parse_address(start)?, parse_address(end)?),
+        None => (parse_address(range)?, parse_address(range)?),
+    };
+
+    if end < start {
+        return Err(format!("Invalid range: {range}"));
+    }
+
+    Ok(BREAKPOINTS.write().unwrap().add(BreakKind::Watch { start, end, read, write }, condition))
+}
+
+// Called before an instruction is executed, returns the breakpoint that stops at PC
+pub fn pc_check(cpu: &CPUContext, ppu: &PPUContext) -> Option<String> {
+    let breakpoints = BREAKPOINTS.read().unwrap();
+    let pc = cpu.registers.pc;
+
+    breakpoints
+        .list
+        .iter()
+        .find(|b| match b.kind {
+            BreakKind::Pc { address, bank } => {
+                address == pc
+                    && bank.is_none_or(|bank| CART.read().unwrap().rom_bank(pc) == Some(bank))
+                    && b.condition_holds(cpu, ppu)
+            }
+            _ => false,
+        })
+        .map(|b| format!("Hit {}", b.describe()))
+}
+
+// Called from bus_read and bus_write, value is Some for writes
+pub fn watch_check(cpu: &CPUContext, ppu: &PPUContext, address: u16, value: Option<u8>) {
+    let mut breakpoints = BREAKPOINTS.write().unwrap();
+
+    let hit = breakpoints.list.iter().find(|b| match b.kind {
+        BreakKind::Watch { start, end, read, write } => {
+            start <= address && address <= end
+                && if value.is_some() { write } else { read }
+                && b.condition_holds(cpu, ppu)
+        }
+        _ => false,
+    });
+
+    if let Some(b) = hit {
+        let access = match value {
+            Some(value) => format!("write of ${value:02X} to"),
+            None => String::from("read of"),
+        };
+
+        breakpoints.hit = Some(format!("Hit {} ({access} ${address:04X})", b.describe()));
+    }
+}
+
+// Called when an interrupt is dispatched
+pub fn interrupt_check(cpu: &CPUContext, ppu: &PPUContext, it: InterruptType) {
+    let mut breakpoints = BREAKPOINTS.write().unwrap();
+
+    let hit = breakpoints.list.iter().find(|b| match b.kind {
+        BreakKind::Interrupt(other) => other.is_none_or(|other| other as u8 == it as u8) && b.condition_holds(cpu, ppu),
+        _ => false,
+    });
+
+    if let Some(b) = hit {
+        breakpoints.hit = Some(format!("Hit {} ({} interrupt)", b.describe(), interrupt_name(it)));
+    }
+}
+
+pub fn take_hit() -> Option<String> {
+    BREAKPOINTS.write().unwrap().hit.take()
+}
@@ -1,26 +1,160 @@
-use super::{cart::CART, ram::RAM, io::{io_read, io_write}, cpu::CPUContext, ppu::PPUContext, dma::DMA};
+use super::{cart::CART, emu::EMULATOR, interrupts::InterruptType, ram::RAM, io::{io_read, io_write}, cpu::CPUContext, ppu::PPUContext, dma::DMA, boot::BOOT, model::{Model, MODEL}, breakpoints::{watching, watch_check, interrupt_check}, trace::{tracing, trace_doctor, trace_instruction, TraceFormat}, mooneye::{mooneye_check, LD_B_B}};
+
+// What the CPU executes against, every access and every M-cycle the CPU uses up goes through here
+pub trait Bus {
+    fn read(&mut self, cpu: &CPUContext, address: u16) -> u8;
+    fn write(&mut self, cpu: &mut CPUContext, address: u16, value: u8);
+
+    // Lets the rest of the machine run for the M-cycles the CPU spent
+    fn cycles(&mut self, cpu: &mut CPUContext, m_cycles: u8);
+
+    // Hooks for the debugging features around the CPU, nothing happens on a bare bus. instruction_decoded comes once
+    // the operands are fetched too, with the pc the instruction started at
+    fn instruction_start(&mut self, _cpu: &CPUContext) {}
+    fn instruction_decoded(&mut self, _cpu: &CPUContext, _pc: u16) {}
+    fn interrupt_taken(&mut self, _cpu: &CPUContext, _it: InterruptType) {}
+
+    fn read16(&mut self, cpu: &CPUContext, address: u16) -> u16 {
+        let lo = self.read(cpu, address) as u16;
+        let hi = self.read(cpu, address.wrapping_add(1)) as u16;
+
+        (hi << 8) | lo
+    }
+
+    fn write16(&mut self, cpu: &mut CPUContext, address: u16, value: u16) {
+        self.write(cpu, address.wrapping_add(1), (value >> 8) as u8);
+        self.write(cpu, address, value as u8);
+    }
+}
+
+// The Game Boy's memory map, the timer, PPU and DMA tick along with the CPU
+pub struct MemoryMap<'a> {
+    pub ppu: &'a mut PPUContext,
+}
+
+impl Bus for MemoryMap<'_> {
+    fn read(&mut self, cpu: &CPUContext, address: u16) -> u8 {
+        bus_read(cpu, self.ppu, address)
+    }
+
+    fn write(&mut self, cpu: &mut CPUContext, address: u16, value: u8) {
+        bus_write(cpu, self.ppu, address, value);
+    }
+
+    fn cycles(&mut self, cpu: &mut CPUContext, m_cycles: u8) {
+        EMULATOR.write().unwrap().cycles(cpu, self.ppu, m_cycles);
+    }
+
+    fn instruction_start(&mut self, cpu: &CPUContext) {
+        if tracing() == Some(TraceFormat::Doctor) {
+            trace_doctor(cpu, self);
+        }
+    }
+
+    fn instruction_decoded(&mut self, cpu: &CPUContext, pc: u16) {
+        if cpu.cur_opcode == LD_B_B {
+            mooneye_check(cpu);
+        }
+
+        if tracing() == Some(TraceFormat::Full) {
+            trace_instruction(cpu, self, pc);
+        }
+    }
+
+    fn interrupt_taken(&mut self, cpu: &CPUContext, it: InterruptType) {
+        interrupt_check(cpu, self.ppu, it);
+    }
+}
+
+pub struct BusAccess {
+    pub cycle: u64, // M-cycle the access happened in, counted from the last reset of FlatBus::cycles
+    pub address: u16,
+    pub value: u8,
+    pub write: bool,
+}
+
+// 64 KB of plain RAM and nothing else, so the CPU can be tested on its own. Every access is logged
+pub struct FlatBus {
+    pub memory: Vec<u8>,
+    pub log: Vec<BusAccess>,
+    pub cycles: u64, // M-cycles
+}
+
+impl Default for FlatBus {
+    fn default() -> Self {
+        FlatBus {
+            memory: vec![0; 0x10000],
+            log: Vec::new(),
+            cycles: 0,
+        }
+    }
+}
+
+impl Bus for FlatBus {
+    fn read(&mut self, _cpu: &CPUContext, address: u16) -> u8 {
+        let value = self.memory[address as usize];
+        self.log.push(BusAccess { cycle: self.cycles, address, value, write: false });
+
+        value
+    }
+
+    fn write(&mut self, _cpu: &mut CPUContext, address: u16, value: u8) {
+        self.memory[address as usize] = value;
+        self.log.push(BusAccess { cycle: self.cycles, address, value, write: true });
+    }
+
+    fn cycles(&mut self, _cpu: &mut CPUContext, m_cycles: u8) {
+        self.cycles += m_cycles as u64;
+    }
+}
 
 pub fn bus_read(cpu: &CPUContext, ppu: &PPUContext, address: u16) -> u8 {
+    // Before taking any locks, conditions read memory through here too
+    if watching() {
+        watch_check(cpu, ppu, address, None);
+    }
+
     let cart = CART.read().unwrap();
     let ram = RAM.read().unwrap();
     match address {
+        addr if addr < 0x0900 && BOOT.read().unwrap().maps(addr) => BOOT.read().unwrap().read(address), // Boot ROM
         addr if addr < 0x8000 => cart.read(address),     // ROM data
         addr if addr < 0xA000 => ppu.vram_read(address), // Char/map data
         addr if addr < 0xC000 => cart.read(address),     // Cartridge RAM
         addr if addr < 0xE000 => ram.wram_read(address), // WRAM (Working RAM)
-        addr if addr < 0xFE00 => 0,                      // Reserved echo RAM
+        addr if addr < 0xFE00 => ram.wram_read(address - 0x2000), // Echo RAM
         addr if addr < 0xFEA0 => {                       // OAM
             if DMA.read().unwrap().transferring() {return 0xFF;}
             ppu.oam_read(address)
         },
-        addr if addr < 0xFF00 => 0,                      // Unusable reserved,
+        addr if addr < 0xFF00 => unusable_read(address), // Unusable reserved
         addr if addr < 0xFF80 => io_read(cpu, address),  // I/O Registers
-        addr if addr == 0xFFFF => cpu.get_ie_reg(),      // CPU enable register
+        0xFFFF => cpu.get_ie_reg(),                      // CPU enable register
         _ => ram.hram_read(address)                           // HRAM (High RAM)
     }
 }
 
+// What reads from 0xFEA0 - 0xFEFF return, which depends on the model
+fn unusable_read(address: u16) -> u8 {
+    if *MODEL.read().unwrap() == Model::CGB {
+        // NOTICE: CGB revision E, the high nibble of the low address byte twice. Earlier revisions differ
+        let nibble = (address as u8) >> 4;
+        return (nibble << 4) | nibble;
+    }
+
+    // Blocked along with OAM
+    if DMA.read().unwrap().transferring() {
+        return 0xFF;
+    }
+
+    0x00
+}
+
 pub fn bus_write(cpu: &mut CPUContext, ppu: &mut PPUContext, address: u16, value: u8) {
+    if watching() {
+        watch_check(cpu, ppu, address, Some(value));
+    }
+
     let mut cart = CART.write().unwrap();
     let mut ram = RAM.write().unwrap();
     match address {
@@ -28,30 +162,18 @@ pub fn bus_write(cpu: &mut CPUContext, ppu: &mut PPUContext, address: u16, value
         addr if addr < 0xA000 => ppu.vram_write(address, value),                                       // Char/map data
         addr if addr < 0xC000 => cart.write(address, value),                                           // Cartridge RAM
         addr if addr < 0xE000 => ram.wram_write(address, value),                                       // WRAM (Working RAM)
-        addr if addr < 0xFE00 => println!("UNSUPPORTED: Bus.write({address:04X}): Reserved echo RAM"), // Reserved echo RAM
+        addr if addr < 0xFE00 => ram.wram_write(address - 0x2000, value),                              // Echo RAM
         addr if addr < 0xFEA0 => {                                                                     // OAM
             if DMA.read().unwrap().transferring() {return;}
             ppu.oam_write(address, value);
         },
-        addr if addr < 0xFF00 => println!("UNSUPPORTED: Bus.write({address:04X}): Unusable reserved"), // Unusable reserved,
+        addr if addr < 0xFF00 => {},                                                                   // Unusable reserved, writes are ignored
         addr if addr < 0xFF80 => io_write(cpu, address, value),                                        // I/O Registers
-        addr if addr == 0xFFFF => cpu.set_ie_reg(value),                                               // CPU enable register
+        0xFFFF => cpu.set_ie_reg(value),                                                               // CPU enable register
         _ => ram.hram_write(address, value)                                                                 // HRAM (High RAM)
     }
 }
 
-pub fn bus_read16(cpu: &mut CPUContext, ppu: &PPUContext, address: u16) -> u16 {
-    let lo = bus_read(cpu, ppu, address) as u16;
-    let hi = bus_read(cpu, ppu, address + 1) as u16;
-
-    return (hi << 8) | lo;
-}
-
-pub fn bus_write16(cpu: &mut CPUContext, ppu: &mut PPUContext, address: u16, value: u16) {
-    bus_write(cpu, ppu, address + 1, (value >> 8) as u8);
-    bus_write(cpu, ppu, address, value as u8);
-}
-
 // 0x0000 - 0x3FFF : ROM Bank 0
 // 0x4000 - 0x7FFF : ROM Bank 1 - Switchable
 // 0x8000 - 0x97FF : CHR RAM
@@ -60,7 +182,7 @@ pub fn bus_write16(cpu: &mut CPUContext, ppu: &mut PPUContext, address: u16, val
 // 0xA000 - 0xBFFF : Cartridge RAM
 // 0xC000 - 0xCFFF : RAM Bank 0
 // 0xD000 - 0xDFFF : RAM Bank 1-7 - switchable - Color only
-// 0xE000 - 0xFDFF : Reserved - Echo RAM
+// 0xE000 - 0xFDFF : Echo RAM - Mirror of 0xC000 - 0xDDFF
 // 0xFE00 - 0xFE9F : Object Attribute Memory
 // 0xFEA0 - 0xFEFF : Reserved - Unusable
 // 0xFF00 - 0xFF7F : I/O Registers
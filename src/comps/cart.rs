@@ -1,4 +1,6 @@
-use std::{fs::File, os::unix::fs::MetadataExt, io::Read, sync::RwLock};
+use std::{fs::File, os::unix::fs::MetadataExt, io::{self, Read}, sync::RwLock};
+
+use super::log::{log, Category, Level};
 
 pub struct ROMHeader {
     _entry: [u8; 4],
@@ -13,8 +15,8 @@ pub struct ROMHeader {
     _dest_code: u8,
     lic_code: u8,
     version: u8,
-    checksum: u8,
-    _global_checksum: u16,
+    pub checksum: u8,
+    pub global_checksum: u16,
 }
 
 impl From<&Vec<u8>> for ROMHeader {
@@ -22,7 +24,7 @@ impl From<&Vec<u8>> for ROMHeader {
         ROMHeader {
             _entry: rom_data[0x100..=0x103].try_into().unwrap(),
             _logo: rom_data[0x104..=0x133].try_into().unwrap(),
-            title: rom_data[0x134..=0x143].into_iter().map(|val| *val as char).collect::<Vec<char>>().try_into().unwrap(),
+            title: rom_data[0x134..=0x143].iter().map(|val| *val as char).collect::<Vec<char>>().try_into().unwrap(),
             _new_lic_code: (rom_data[0x144] as u16) << 8 | (rom_data[0x145] as u16),
             _sgb_flag: rom_data[0x146],
             type_: rom_data[0x147],
@@ -32,7 +34,7 @@ impl From<&Vec<u8>> for ROMHeader {
             lic_code: rom_data[0x14b],
             version: rom_data[0x14c],
             checksum: rom_data[0x14d],
-            _global_checksum: (rom_data[0x14e] as u16) << 8 | (rom_data[0x14f] as u16),
+            global_checksum: (rom_data[0x14e] as u16) << 8 | (rom_data[0x14f] as u16),
         }
     }
 }
@@ -61,39 +63,37 @@ pub static CART: RwLock<CartContext> = RwLock::new(CartContext {
         lic_code: 0,
         version: 0,
         checksum: 0,
-        _global_checksum: 0
+        global_checksum: 0
     }
 });
 
 impl CartContext {
-    pub fn load(&mut self, args: Vec<String>) {
-        if args.len() < 2 {
-            panic!("Usage: cargo run <rom_file> \n");
-        }
-
-        let filename = &args[1];
-
+    pub fn load(&mut self, filename: &str) -> io::Result<()> {
         // Open the file
-        println!("Filename: {filename}");
-        let mut file = File::open(format!("/Users/lassegrosbol-rais/Desktop/gbemu/roms/{filename}")).unwrap();
-        println!("Opened: {filename}");
+        log(Category::Cart, Level::Debug, format_args!("Filename: {filename}"));
+        let mut file = File::open(filename)?;
+        log(Category::Cart, Level::Info, format_args!("Opened: {filename}"));
 
         // Extract the data
-        self.rom_size = file.metadata().unwrap().size() as u32;
+        self.rom_size = file.metadata()?.size() as u32;
         self.rom_data = Vec::with_capacity(self.rom_size as usize);
-        file.read_to_end(&mut self.rom_data).unwrap();
+        file.read_to_end(&mut self.rom_data)?;
+
+        if self.rom_data.len() < 0x150 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "ROM is too small to contain a cartridge header"));
+        }
 
         // Create ROMHeader from data
         self.header = ROMHeader::from(&self.rom_data);
 
         // Display data
-        println!("Cartridge Loaded:");
-        println!("\t Title    : {}", self.header.title.iter().collect::<String>());
-        println!("\t Type     : {:02X} ({})", self.header.type_, ROM_TYPES[self.header.type_ as usize]);
-        println!("\t ROM Size : {} KB", 32 << self.header.rom_size);
-        println!("\t RAM Size : {:02X}", self.header.ram_size);
-        println!("\t LIC Code : {:02X} ({})", self.header.lic_code, lic_code(self.header.lic_code));
-        println!("\t ROM Vers : {:02X}", self.header.version);
+        log(Category::Cart, Level::Info, format_args!("Cartridge Loaded:"));
+        log(Category::Cart, Level::Info, format_args!("  Title    : {}", self.header.title.iter().collect::<String>()));
+        log(Category::Cart, Level::Info, format_args!("  Type     : {:02X} ({})", self.header.type_, ROM_TYPES[self.header.type_ as usize]));
+        log(Category::Cart, Level::Info, format_args!("  ROM Size : {} KB", 32 << self.header.rom_size));
+        log(Category::Cart, Level::Info, format_args!("  RAM Size : {:02X}", self.header.ram_size));
+        log(Category::Cart, Level::Info, format_args!("  LIC Code : {:02X} ({})", self.header.lic_code, lic_code(self.header.lic_code)));
+        log(Category::Cart, Level::Info, format_args!("  ROM Vers : {:02X}", self.header.version));
 
         // Validate checksum
         let mut x: u16 = 0;
@@ -102,7 +102,7 @@ impl CartContext {
             x = x.wrapping_sub(self.rom_data[i] as u16).wrapping_sub(1);
         }
 
-        println!("\t Checksum : {:02X} ({})", self.header.checksum, if x as u8 != 0 {"PASSED"} else {"FAILED"});
+        log(Category::Cart, Level::Info, format_args!("  Checksum : {:02X} ({})", self.header.checksum, if x as u8 != 0 {"PASSED"} else {"FAILED"}));
 
         // Convert filename from &str to [char; 1024]
         let chars = filename.chars();
@@ -111,14 +111,33 @@ impl CartContext {
         for (i, c) in chars.enumerate() {
             filename[i] = c;
         }
+
+        Ok(())
     }
 
+    // NOTICE: Cartridge RAM isn't emulated, addresses past the end of the ROM read 0xFF and ignore writes
     pub fn read(&self, address: u16) -> u8 {
-        self.rom_data[address as usize]
+        self.rom_data.get(address as usize).copied().unwrap_or(0xFF)
     }
 
     pub fn write(&mut self, address: u16, value: u8) {
-        self.rom_data[address as usize] = value;
+        if address < 0x8000 {
+            log(Category::Cart, Level::Debug, format_args!("ROM write: {address:04X} = {value:02X}, there is no mapper"));
+        }
+
+        if let Some(byte) = self.rom_data.get_mut(address as usize) {
+            *byte = value;
+        }
+    }
+
+    // ROM bank mapped at address, None outside of ROM
+    // NOTICE: There is no mapper yet, so 0x4000 - 0x7FFF is always bank 1
+    pub fn rom_bank(&self, address: u16) -> Option<u16> {
+        match address {
+            0x0000..=0x3FFF => Some(0),
+            0x4000..=0x7FFF => Some(1),
+            _ => None,
+        }
     }
 }
 
@@ -161,7 +180,7 @@ const ROM_TYPES: [&str; 35] = [
 ];
 
 fn lic_code(code: u8) -> &'static str {
-    return match code {
+    match code {
         0x00 => "None",
         0x01 => "Nintendo R&D1",
         0x08 => "Capcom",
@@ -1,6 +1,4 @@
-use std::{time::{Duration, Instant}, sync::RwLock};
-
-pub static TIME: RwLock<Option<Instant>> = RwLock::new(None);
+use std::{time::Duration, sync::RwLock};
 
 pub static COLORS: [u32; 4] = [
     0xFFFFFFFF,
@@ -9,6 +7,9 @@ pub static COLORS: [u32; 4] = [
     0xFF000000
 ];
 
+// Colors the LCD palettes map shades onto, COLORS unless changed by the frontend
+pub static PALETTE: RwLock<[u32; 4]> = RwLock::new(COLORS);
+
 pub fn bit(a: u8, n: u8) -> bool {
     (a & (1 << n)) != 0
 }
@@ -24,6 +25,16 @@ pub fn bit_set(a: &mut u8, n: u8, on: bool) {
     *a &= !(1 << n);
 }
 
+// A hexadecimal number, optionally prefixed with either $ or 0x
+pub fn parse_hex(arg: &str) -> Option<u16> {
+    let digits = arg.strip_prefix('$').or_else(|| arg.strip_prefix("0x")).unwrap_or(arg);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    u16::from_str_radix(digits, 16).ok()
+}
+
 pub fn between(a: u16, b: u16, c: u16) -> bool {
     b <= a && a <= c
 }
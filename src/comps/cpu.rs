@@ -1,8 +1,8 @@
 use std::sync::RwLock;
 
-use crate::comps::{instructions::AddrMode, emu::EMULATOR, bus::bus_read, ppu::PPU};
+use crate::comps::{instructions::AddrMode, bus::Bus};
 
-use super::{instructions::{Instruction, INSTRUCTIONS}, common::*, cpu_proc::proc_by_inst, interrupts::*, ppu::PPUContext};
+use super::{instructions::{Instruction, INSTRUCTIONS}, common::*, cpu_proc::proc_by_inst, interrupts::*};
 
 pub struct CPUContext {
     pub registers: Registers,
@@ -19,69 +19,65 @@ pub struct CPUContext {
     pub ie_register: u8,
 }
 
-pub static CPU: RwLock<CPUContext> = RwLock::new(CPUContext {
-    registers: Registers {
-        pc: 0x100,
-        sp: 0xFFFE,
-        a: 0x01,
-        f: 0xB0,
-        b: 0x00,
-        c: 0x13,
-        d: 0x00,
-        e: 0xD8,
-        h: 0x01,
-        l: 0x4D,
-    },
-    ie_register: 0,
-    int_flags: 0,
-    int_master_enabled: false,
-    enabling_ime: false,
-
-    fetched_data: 0,
-    mem_dest: 0,
-    dest_is_mem: false,
-    cur_opcode: 0,
-    cur_inst: &INSTRUCTIONS[0],
-    halted: false,
-    stepping: true,
-});
+pub static CPU: RwLock<CPUContext> = RwLock::new(CPUContext::new());
+
+impl Default for CPUContext {
+    fn default() -> Self {
+        CPUContext::new()
+    }
+}
 
 impl CPUContext {
-    pub fn step(&mut self, ppu: &mut PPUContext) {
+    // The registers as the boot ROM leaves them
+    pub const fn new() -> Self {
+        CPUContext {
+            registers: Registers {
+                pc: 0x100,
+                sp: 0xFFFE,
+                a: 0x01,
+                f: 0xB0,
+                b: 0x00,
+                c: 0x13,
+                d: 0x00,
+                e: 0xD8,
+                h: 0x01,
+                l: 0x4D,
+            },
+            ie_register: 0,
+            int_flags: 0,
+            int_master_enabled: false,
+            enabling_ime: false,
+
+            fetched_data: 0,
+            mem_dest: 0,
+            dest_is_mem: false,
+            cur_opcode: 0,
+            cur_inst: &INSTRUCTIONS[0],
+            halted: false,
+            stepping: false,
+        }
+    }
+
+    pub fn step(&mut self, bus: &mut dyn Bus) {
         if !self.halted {
-            // let pc = self.registers.pc;
-            self.fetch_instruction(ppu);
-
-            // println!("{:08X} - ${:04X}: {:14} ({:02X} {:02X} {:02X}) A: {:02X} F: {:04b} BC: {:02X}{:02X} DE: {:02X}{:02X} HL: {:02X}{:02X}",
-            //     EMULATOR.read().unwrap().ticks,
-            //     pc,
-            //     self.inst_string(ppu),
-            //     self.cur_opcode,
-            //     bus_read(self, ppu, pc + 1),
-            //     bus_read(self, ppu, pc + 2),
-            //     self.registers.a,
-            //     self.registers.f >> 4,
-            //     self.registers.b,
-            //     self.registers.c,
-            //     self.registers.d,
-            //     self.registers.e,
-            //     self.registers.h,
-            //     self.registers.l,
-            // );
-            
-            EMULATOR.write().unwrap().cycles(self, ppu, 1);
-            self.fetch_data(ppu);
-
-            self.execute(ppu);
+            let pc = self.registers.pc;
+            bus.instruction_start(self);
+
+            self.fetch_instruction(bus);
+            bus.cycles(self, 1);
+            self.fetch_data(bus);
+
+            bus.instruction_decoded(self, pc);
+            self.execute(bus);
         } else {
-            EMULATOR.write().unwrap().cycles(self, ppu, 1);
+            bus.cycles(self, 1);
             if self.int_flags != 0 {
                 self.halted = false;
             }
         }
 
         if self.int_master_enabled {
-            handle_interrupts(self, ppu);
+            handle_interrupts(self, bus);
             self.enabling_ime = false;
         }
 
@@ -90,10 +86,10 @@ impl CPUContext {
         }
     }
 
-    fn execute(&mut self, ppu: &mut PPUContext) {
+    fn execute(&mut self, bus: &mut dyn Bus) {
         let proc = proc_by_inst(self.cur_inst.inst_type);
 
-        proc(self, ppu);
+        proc(self, bus);
     }
 
     pub fn set_flags(&mut self, z: Option<bool>, n: Option<bool>, h: Option<bool>, c: Option<bool>) {
@@ -144,14 +140,14 @@ impl CPUContext {
         self.int_flags |= int_type as u8;
     }
 
-    fn inst_string(&self, ppu: &PPUContext) -> String {
+    pub fn inst_string(&self, bus: &mut dyn Bus) -> String {
         type AM = AddrMode;
         let inst = self.cur_inst;
 
         format!("{} {}",
             self.cur_inst.inst_type,
             match self.cur_inst.mode {
-                AM::IMP => format!(""),
+                AM::IMP => String::new(),
                 AM::RxD16 | AM::RxA16 => format!("{},${:04X}", inst.reg1.unwrap(), self.fetched_data),
                 AM::RxR => format!("{},{}", inst.reg1.unwrap(), inst.reg2.unwrap()),
                 AM::MRxR => format!("({}),{}", inst.reg1.unwrap(), inst.reg2.unwrap()),
@@ -162,11 +158,11 @@ impl CPUContext {
                 AM::RxHLD => format!("{},({}-)", inst.reg1.unwrap(), inst.reg2.unwrap()),
                 AM::HLIxR => format!("({}+),{}", inst.reg1.unwrap(), inst.reg2.unwrap()),
                 AM::HLDxR => format!("({}-),{}", inst.reg1.unwrap(), inst.reg2.unwrap()),
-                AM::A8xR => format!("{},{}", bus_read(self, ppu, self.registers.pc - 1), inst.reg2.unwrap()),
+                AM::A8xR => format!("{},{}", bus.read(self, self.registers.pc.wrapping_sub(1)), inst.reg2.unwrap()),
                 AM::HLxSPR => format!("({}),SP+${:02X}", inst.reg1.unwrap(), self.fetched_data as u8),
                 AM::D16 => format!("${:04X}", self.fetched_data),
                 AM::D8 => format!("${:02X}", self.fetched_data as u8),
-                AM::D16xR => format!(""),
+                AM::D16xR => String::new(),
                 AM::MRxD8 => format!("({}),${:02X}", inst.reg1.unwrap(), self.fetched_data as u8),
                 AM::MR => format!("({})", inst.reg1.unwrap()),
                 AM::A16xR => format!("(${:04X}),{}", self.fetched_data, inst.reg2.unwrap()),
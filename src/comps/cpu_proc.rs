@@ -4,27 +4,26 @@ use crate::comps::{
 };
 
 use super::{
-    bus::{bus_read, bus_write, bus_write16},
+    bus::Bus,
     cpu::CPUContext,
-    emu::EMULATOR, ppu::PPUContext,
 };
 
-fn proc_none(_cpu: &mut CPUContext, _ppu: &mut PPUContext) {
+fn proc_none(_cpu: &mut CPUContext, _bus: &mut dyn Bus) {
     panic!("INVALID INSTRUCTION!\n")
 }
 
-fn proc_nop(_cpu: &mut CPUContext, _ppu: &mut PPUContext) {}
+fn proc_nop(_cpu: &mut CPUContext, _bus: &mut dyn Bus) {}
 
-fn proc_ld(cpu: &mut CPUContext, ppu: &mut PPUContext) {
+fn proc_ld(cpu: &mut CPUContext, bus: &mut dyn Bus) {
     if cpu.dest_is_mem {
         if cpu.cur_inst.reg2.is_some() && is_16_bit(cpu.cur_inst.reg2.unwrap()) {
-            EMULATOR.write().unwrap().cycles(cpu, ppu, 1);
-            bus_write16(cpu, ppu, cpu.mem_dest, cpu.fetched_data);
+            bus.cycles(cpu, 1);
+            bus.write16(cpu, cpu.mem_dest, cpu.fetched_data);
         } else {
-            bus_write(cpu, ppu, cpu.mem_dest, cpu.fetched_data as u8);
+            bus.write(cpu, cpu.mem_dest, cpu.fetched_data as u8);
         }
 
-        EMULATOR.write().unwrap().cycles(cpu, ppu, 1);
+        bus.cycles(cpu, 1);
 
         return;
     }
@@ -44,17 +43,17 @@ fn proc_ld(cpu: &mut CPUContext, ppu: &mut PPUContext) {
     cpu.set_reg(cpu.cur_inst.reg1, cpu.fetched_data);
 }
 
-fn proc_inc(cpu: &mut CPUContext, ppu: &mut PPUContext) {
+fn proc_inc(cpu: &mut CPUContext, bus: &mut dyn Bus) {
     let mut val = cpu.read_reg(cpu.cur_inst.reg1).wrapping_add(1);
 
     if is_16_bit(cpu.cur_inst.reg1.unwrap()) {
-        EMULATOR.write().unwrap().cycles(cpu, ppu, 1);
+        bus.cycles(cpu, 1);
     }
 
     if cpu.cur_inst.reg1.unwrap() == RegType::HL && cpu.cur_inst.mode == AddrMode::MR {
         let address = cpu.read_reg(Some(RegType::HL));
-        val = (bus_read(cpu, ppu, address) as u16 + 1) & 0xFF;
-        bus_write(cpu, ppu, address, val as u8);
+        val = (bus.read(cpu, address) as u16 + 1) & 0xFF;
+        bus.write(cpu, address, val as u8);
     } else {
         cpu.set_reg(cpu.cur_inst.reg1, val);
         val = cpu.read_reg(cpu.cur_inst.reg1);
@@ -67,17 +66,17 @@ fn proc_inc(cpu: &mut CPUContext, ppu: &mut PPUContext) {
     cpu.set_flags(Some(val == 0), Some(false), Some(val & 0xF == 0), None);
 }
 
-fn proc_dec(cpu: &mut CPUContext, ppu: &mut PPUContext) {
+fn proc_dec(cpu: &mut CPUContext, bus: &mut dyn Bus) {
     let mut val = cpu.read_reg(cpu.cur_inst.reg1).wrapping_sub(1);
 
     if is_16_bit(cpu.cur_inst.reg1.unwrap()) {
-        EMULATOR.write().unwrap().cycles(cpu, ppu, 1);
+        bus.cycles(cpu, 1);
     }
 
     if cpu.cur_inst.reg1.unwrap() == RegType::HL && cpu.cur_inst.mode == AddrMode::MR {
         let address = cpu.read_reg(Some(RegType::HL));
-        val = (bus_read(cpu, ppu, address) as u16).wrapping_sub(1);
-        bus_write(cpu, ppu, address, val as u8);
+        val = (bus.read(cpu, address) as u16).wrapping_sub(1);
+        bus.write(cpu, address, val as u8);
     } else {
         cpu.set_reg(cpu.cur_inst.reg1, val);
         val = cpu.read_reg(cpu.cur_inst.reg1);
@@ -90,7 +89,7 @@ fn proc_dec(cpu: &mut CPUContext, ppu: &mut PPUContext) {
     cpu.set_flags(Some(val == 0), Some(true), Some(val & 0xF == 0xF), None)
 }
 
-fn proc_rlca(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
+fn proc_rlca(cpu: &mut CPUContext, _bus: &mut dyn Bus) {
     let mut u = cpu.registers.a;
     let c = (u >> 7) & 1;
     u = (u << 1) | c;
@@ -99,16 +98,18 @@ fn proc_rlca(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
     cpu.set_flags(Some(false), Some(false), Some(false), Some(c != 0));
 }
 
-fn proc_add(cpu: &mut CPUContext, ppu: &mut PPUContext) {
+fn proc_add(cpu: &mut CPUContext, bus: &mut dyn Bus) {
     let mut val = cpu.read_reg(cpu.cur_inst.reg1) as u32 + cpu.fetched_data as u32;
 
     let is_16bit = is_16_bit(cpu.cur_inst.reg1.unwrap());
 
     if is_16bit {
-        EMULATOR.write().unwrap().cycles(cpu, ppu, 1);
+        bus.cycles(cpu, 1);
     }
 
     if cpu.cur_inst.reg1.unwrap() == RegType::SP {
+        // ADD SP,e8 takes a second internal cycle
+        bus.cycles(cpu, 1);
         val = (cpu.read_reg(cpu.cur_inst.reg1) as i32 + (cpu.fetched_data as i8) as i32) as u32;
     }
 
@@ -133,7 +134,7 @@ fn proc_add(cpu: &mut CPUContext, ppu: &mut PPUContext) {
     cpu.set_flags(z, Some(false), h, c);
 }
 
-fn proc_rrca(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
+fn proc_rrca(cpu: &mut CPUContext, _bus: &mut dyn Bus) {
     let b = cpu.registers.a & 1;
     cpu.registers.a >>= 1;
     cpu.registers.a |= b << 7;
@@ -141,11 +142,11 @@ fn proc_rrca(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
     cpu.set_flags(Some(false), Some(false), Some(false), Some(b != 0));
 }
 
-fn proc_stop(_cpu: &mut CPUContext, _ppu: &mut PPUContext) {
+fn proc_stop(_cpu: &mut CPUContext, _bus: &mut dyn Bus) {
     panic!("STOP");
 }
 
-fn proc_rla(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
+fn proc_rla(cpu: &mut CPUContext, _bus: &mut dyn Bus) {
     let u = cpu.registers.a;
     let c_flag = cpu.flag_c();
     let c = (u >> 7) & 1;
@@ -154,13 +155,13 @@ fn proc_rla(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
     cpu.set_flags(Some(false), Some(false), Some(false), Some(c != 0));
 }
 
-fn proc_jr(cpu: &mut CPUContext, ppu: &mut PPUContext) {
+fn proc_jr(cpu: &mut CPUContext, bus: &mut dyn Bus) {
     let rel = cpu.fetched_data as i8;
     let addr = (cpu.registers.pc as i32 + rel as i32) as u16;
-    goto_addr(cpu, ppu, addr, false);
+    goto_addr(cpu, bus, addr, false);
 }
 
-fn proc_rra(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
+fn proc_rra(cpu: &mut CPUContext, _bus: &mut dyn Bus) {
     let carry = cpu.flag_c() as u8;
     let new_c = cpu.registers.a & 1;
 
@@ -170,7 +171,7 @@ fn proc_rra(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
     cpu.set_flags(Some(false), Some(false), Some(false), Some(new_c != 0));
 }
 
-fn proc_daa(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
+fn proc_daa(cpu: &mut CPUContext, _bus: &mut dyn Bus) {
     let mut u = 0;
     let mut fc = 0;
 
@@ -194,25 +195,25 @@ fn proc_daa(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
     cpu.set_flags(Some(flag_z), None, Some(false), Some(fc != 0));
 }
 
-fn proc_cpl(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
+fn proc_cpl(cpu: &mut CPUContext, _bus: &mut dyn Bus) {
     cpu.registers.a = !cpu.registers.a;
     cpu.set_flags(None, Some(true), Some(true), None);
 }
 
-fn proc_scf(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
+fn proc_scf(cpu: &mut CPUContext, _bus: &mut dyn Bus) {
     cpu.set_flags(None, Some(false), Some(false), Some(true));
 }
 
-fn proc_ccf(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
+fn proc_ccf(cpu: &mut CPUContext, _bus: &mut dyn Bus) {
     let flag_c = cpu.flag_c() as u8;
     cpu.set_flags(None, Some(false), Some(false), Some(flag_c ^ 1 != 0));
 }
 
-fn proc_halt(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
+fn proc_halt(cpu: &mut CPUContext, _bus: &mut dyn Bus) {
     cpu.halted = true;
 }
 
-fn proc_adc(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
+fn proc_adc(cpu: &mut CPUContext, _bus: &mut dyn Bus) {
     let u = cpu.fetched_data;
     let a = cpu.registers.a as u16;
     let c = cpu.flag_c() as u16;
@@ -225,7 +226,7 @@ fn proc_adc(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
     cpu.set_flags(Some(flag_z), Some(false), Some(flag_h), Some(flag_c))
 }
 
-fn proc_sub(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
+fn proc_sub(cpu: &mut CPUContext, _bus: &mut dyn Bus) {
     let val = cpu
         .read_reg(cpu.cur_inst.reg1)
         .wrapping_sub(cpu.fetched_data);
@@ -238,7 +239,7 @@ fn proc_sub(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
     cpu.set_flags(Some(z), Some(true), Some(h), Some(c));
 }
 
-fn proc_sbc(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
+fn proc_sbc(cpu: &mut CPUContext, _bus: &mut dyn Bus) {
     let val = (cpu.fetched_data + cpu.flag_c() as u16) as u8;
 
     let z = cpu.read_reg(cpu.cur_inst.reg1).wrapping_sub(val as u16) == 0;
@@ -255,25 +256,25 @@ fn proc_sbc(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
     cpu.set_flags(Some(z), Some(true), Some(h), Some(c));
 }
 
-fn proc_and(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
+fn proc_and(cpu: &mut CPUContext, _bus: &mut dyn Bus) {
     cpu.registers.a &= cpu.fetched_data as u8;
     let flag_z = cpu.registers.a == 0;
     cpu.set_flags(Some(flag_z), Some(false), Some(true), Some(false));
 }
 
-fn proc_xor(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
+fn proc_xor(cpu: &mut CPUContext, _bus: &mut dyn Bus) {
     cpu.registers.a ^= cpu.fetched_data as u8;
     let flag_z = cpu.registers.a == 0;
     cpu.set_flags(Some(flag_z), Some(false), Some(false), Some(false));
 }
 
-fn proc_or(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
+fn proc_or(cpu: &mut CPUContext, _bus: &mut dyn Bus) {
     cpu.registers.a |= cpu.fetched_data as u8;
     let flag_z = cpu.registers.a == 0;
     cpu.set_flags(Some(flag_z), Some(false), Some(false), Some(false));
 }
 
-fn proc_cp(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
+fn proc_cp(cpu: &mut CPUContext, _bus: &mut dyn Bus) {
     let n = cpu.registers.a as i32 - cpu.fetched_data as i32;
     let flag_h = (cpu.registers.a & 0xF)
         .checked_sub((cpu.fetched_data & 0xF) as u8)
@@ -281,12 +282,12 @@ fn proc_cp(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
     cpu.set_flags(Some(n == 0), Some(true), Some(flag_h), Some(n < 0))
 }
 
-fn proc_pop(cpu: &mut CPUContext, ppu: &mut PPUContext) {
-    let lo = stack_pop(cpu, ppu) as u16;
-    EMULATOR.write().unwrap().cycles(cpu, ppu, 1);
+fn proc_pop(cpu: &mut CPUContext, bus: &mut dyn Bus) {
+    let lo = stack_pop(cpu, bus) as u16;
+    bus.cycles(cpu, 1);
 
-    let hi = stack_pop(cpu, ppu) as u16;
-    EMULATOR.write().unwrap().cycles(cpu, ppu, 1);
+    let hi = stack_pop(cpu, bus) as u16;
+    bus.cycles(cpu, 1);
 
     let result = (hi << 8) | lo;
 
@@ -297,54 +298,53 @@ fn proc_pop(cpu: &mut CPUContext, ppu: &mut PPUContext) {
     }
 }
 
-fn proc_jp(cpu: &mut CPUContext, ppu: &mut PPUContext) {
-    goto_addr(cpu, ppu, cpu.fetched_data, false);
+fn proc_jp(cpu: &mut CPUContext, bus: &mut dyn Bus) {
+    goto_addr(cpu, bus, cpu.fetched_data, false);
 }
 
-fn proc_push(cpu: &mut CPUContext, ppu: &mut PPUContext) {
+fn proc_push(cpu: &mut CPUContext, bus: &mut dyn Bus) {
     let reg_1 = cpu.read_reg(cpu.cur_inst.reg1);
     let hi = (reg_1 >> 8) as u8;
-    EMULATOR.write().unwrap().cycles(cpu, ppu, 1);
-    stack_push(cpu, ppu, hi);
+    bus.cycles(cpu, 1);
+    stack_push(cpu, bus, hi);
 
     let lo = reg_1 as u8;
-    EMULATOR.write().unwrap().cycles(cpu, ppu, 1);
-    stack_push(cpu, ppu, lo);
+    bus.cycles(cpu, 1);
+    stack_push(cpu, bus, lo);
 
-    EMULATOR.write().unwrap().cycles(cpu, ppu, 1);
+    bus.cycles(cpu, 1);
 }
 
-fn proc_ret(cpu: &mut CPUContext, ppu: &mut PPUContext) {
+fn proc_ret(cpu: &mut CPUContext, bus: &mut dyn Bus) {
     if cpu.cur_inst.cond != CondType::NONE {
-        EMULATOR.write().unwrap().cycles(cpu, ppu, 1);
+        bus.cycles(cpu, 1);
     }
 
-    if check_cond(cpu, ppu) {
-        let lo = stack_pop(cpu, ppu) as u16;
-        EMULATOR.write().unwrap().cycles(cpu, ppu, 1);
+    if check_cond(cpu, bus) {
+        let lo = stack_pop(cpu, bus) as u16;
+        bus.cycles(cpu, 1);
 
-        let hi = stack_pop(cpu, ppu) as u16;
-        EMULATOR.write().unwrap().cycles(cpu, ppu, 1);
+        let hi = stack_pop(cpu, bus) as u16;
+        bus.cycles(cpu, 1);
 
         let new_pc = (hi << 8) | lo;
 
         cpu.registers.pc = new_pc;
 
-        EMULATOR.write().unwrap().cycles(cpu, ppu, 1);
+        bus.cycles(cpu, 1);
     }
 }
 
-fn proc_cb(cpu: &mut CPUContext, ppu: &mut PPUContext) {
+fn proc_cb(cpu: &mut CPUContext, bus: &mut dyn Bus) {
     let op = cpu.fetched_data as u8;
     let reg = decode_reg(op & 0b111);
     let bit = (op >> 3) & 0b111;
     let bit_op = (op >> 6) & 0b11;
-    let mut reg_val = cpu.read_reg8(ppu, reg);
-
-    EMULATOR.write().unwrap().cycles(cpu, ppu, 1);
+    let mut reg_val = cpu.read_reg8(bus, reg);
 
+    // The CB byte was fetched as the operand, only (HL) takes extra cycles to read it and write it back
     if reg == RegType::HL {
-        EMULATOR.write().unwrap().cycles(cpu, ppu, 2);
+        bus.cycles(cpu, 1);
     }
 
     match bit_op {
@@ -356,12 +356,12 @@ fn proc_cb(cpu: &mut CPUContext, ppu: &mut PPUContext) {
         2 => {
             // RST
             reg_val &= !(1 << bit);
-            cpu.set_reg8(ppu, reg, reg_val);
+            cpu.set_reg8(bus, reg, reg_val);
         }
         3 => {
             // SET
             reg_val |= 1 << bit;
-            cpu.set_reg8(ppu, reg, reg_val);
+            cpu.set_reg8(bus, reg, reg_val);
         }
         _ => {
             let c_flag = cpu.flag_c() as u8;
@@ -370,14 +370,14 @@ fn proc_cb(cpu: &mut CPUContext, ppu: &mut PPUContext) {
                 0 => {
                     // RLC
                     let mut set_c = false;
-                    let mut result = (reg_val << 1) as u8;
+                    let mut result = reg_val << 1;
 
                     if reg_val & (1 << 7) != 0 {
                         result |= 1;
                         set_c = true;
                     }
 
-                    cpu.set_reg8(ppu, reg, result);
+                    cpu.set_reg8(bus, reg, result);
                     cpu.set_flags(Some(result == 0), Some(false), Some(false), Some(set_c));
                 }
                 1 => {
@@ -386,7 +386,7 @@ fn proc_cb(cpu: &mut CPUContext, ppu: &mut PPUContext) {
                     reg_val >>= 1;
                     reg_val |= old << 7;
 
-                    cpu.set_reg8(ppu, reg, reg_val);
+                    cpu.set_reg8(bus, reg, reg_val);
                     cpu.set_flags(
                         Some(reg_val == 0),
                         Some(false),
@@ -400,7 +400,7 @@ fn proc_cb(cpu: &mut CPUContext, ppu: &mut PPUContext) {
                     reg_val <<= 1;
                     reg_val |= c_flag;
 
-                    cpu.set_reg8(ppu, reg, reg_val);
+                    cpu.set_reg8(bus, reg, reg_val);
                     cpu.set_flags(
                         Some(reg_val == 0),
                         Some(false),
@@ -415,7 +415,7 @@ fn proc_cb(cpu: &mut CPUContext, ppu: &mut PPUContext) {
 
                     reg_val |= c_flag << 7;
 
-                    cpu.set_reg8(ppu, reg, reg_val);
+                    cpu.set_reg8(bus, reg, reg_val);
                     cpu.set_flags(
                         Some(reg_val == 0),
                         Some(false),
@@ -428,7 +428,7 @@ fn proc_cb(cpu: &mut CPUContext, ppu: &mut PPUContext) {
                     let old = reg_val;
                     reg_val <<= 1;
 
-                    cpu.set_reg8(ppu, reg, reg_val);
+                    cpu.set_reg8(bus, reg, reg_val);
                     cpu.set_flags(
                         Some(reg_val == 0),
                         Some(false),
@@ -440,7 +440,7 @@ fn proc_cb(cpu: &mut CPUContext, ppu: &mut PPUContext) {
                     // SRA
                     let u = ((reg_val as i8) >> 1) as u8;
 
-                    cpu.set_reg8(ppu, reg, u);
+                    cpu.set_reg8(bus, reg, u);
                     cpu.set_flags(
                         Some(u == 0),
                         Some(false),
@@ -451,13 +451,13 @@ fn proc_cb(cpu: &mut CPUContext, ppu: &mut PPUContext) {
                 6 => {
                     // SWAP (nibbles)
                     reg_val = ((reg_val & 0xF) << 4) | ((reg_val & 0xF0) >> 4);
-                    cpu.set_reg8(ppu, reg, reg_val);
+                    cpu.set_reg8(bus, reg, reg_val);
                     cpu.set_flags(Some(reg_val == 0), Some(false), Some(false), Some(false));
                 }
                 7 => {
                     // SRL
                     let u = reg_val >> 1;
-                    cpu.set_reg8(ppu, reg, u);
+                    cpu.set_reg8(bus, reg, u);
                     cpu.set_flags(
                         Some(u == 0),
                         Some(false),
@@ -469,51 +469,55 @@ fn proc_cb(cpu: &mut CPUContext, ppu: &mut PPUContext) {
             }
         }
     }
+
+    if reg == RegType::HL && bit_op != 1 {
+        bus.cycles(cpu, 1);
+    }
 }
 
-fn proc_call(cpu: &mut CPUContext, ppu: &mut PPUContext) {
-    goto_addr(cpu, ppu, cpu.fetched_data, true);
+fn proc_call(cpu: &mut CPUContext, bus: &mut dyn Bus) {
+    goto_addr(cpu, bus, cpu.fetched_data, true);
 }
 
-fn proc_reti(cpu: &mut CPUContext, ppu: &mut PPUContext) {
+fn proc_reti(cpu: &mut CPUContext, bus: &mut dyn Bus) {
     cpu.int_master_enabled = true;
-    proc_ret(cpu, ppu);
+    proc_ret(cpu, bus);
 }
 
-fn proc_ldh(cpu: &mut CPUContext, ppu: &mut PPUContext) {
+fn proc_ldh(cpu: &mut CPUContext, bus: &mut dyn Bus) {
     // LDH instructions either have reg1 = Some(RT::A) or reg1 = None
     match cpu.cur_inst.reg1 {
         Some(rt) => {
-            let val = bus_read(cpu, ppu, cpu.fetched_data | 0xFF00) as u16;
+            let val = bus.read(cpu, cpu.fetched_data | 0xFF00) as u16;
             cpu.set_reg(Some(rt), val);
         }
-        None => bus_write(cpu, ppu, cpu.mem_dest | 0xFF00, cpu.registers.a),
+        None => bus.write(cpu, cpu.mem_dest | 0xFF00, cpu.registers.a),
     }
 
-    EMULATOR.write().unwrap().cycles(cpu, ppu, 1);
+    bus.cycles(cpu, 1);
 }
 
-fn proc_jphl(_cpu: &mut CPUContext, _ppu: &mut PPUContext) {
+fn proc_jphl(_cpu: &mut CPUContext, _bus: &mut dyn Bus) {
     panic!("PROCESS NOT YET IMPLEMENTED");
 }
 
-fn proc_di(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
+fn proc_di(cpu: &mut CPUContext, _bus: &mut dyn Bus) {
     cpu.int_master_enabled = false;
 }
 
-fn proc_ei(cpu: &mut CPUContext, _ppu: &mut PPUContext) {
+fn proc_ei(cpu: &mut CPUContext, _bus: &mut dyn Bus) {
     cpu.enabling_ime = true;
 }
 
-fn proc_rst(cpu: &mut CPUContext, ppu: &mut PPUContext) {
-    goto_addr(cpu, ppu, cpu.cur_inst.param.unwrap() as u16, true);
+fn proc_rst(cpu: &mut CPUContext, bus: &mut dyn Bus) {
+    goto_addr(cpu, bus, cpu.cur_inst.param.unwrap() as u16, true);
 }
 
 fn is_16_bit(rt: RegType) -> bool {
     RegType::AF as usize <= rt as usize
 }
 
-fn check_cond(cpu: &mut CPUContext, _ppu: &mut PPUContext) -> bool {
+fn check_cond(cpu: &mut CPUContext, _bus: &mut dyn Bus) -> bool {
     type CT = CondType;
     match cpu.cur_inst.cond {
         CT::NONE => true,
@@ -524,19 +528,24 @@ fn check_cond(cpu: &mut CPUContext, _ppu: &mut PPUContext) -> bool {
     }
 }
 
-fn goto_addr(cpu: &mut CPUContext, ppu: &mut PPUContext, address: u16, push_pc: bool) {
-    if check_cond(cpu, ppu) {
+fn goto_addr(cpu: &mut CPUContext, bus: &mut dyn Bus, address: u16, push_pc: bool) {
+    if check_cond(cpu, bus) {
+        // An internal cycle, then one per byte pushed
         if push_pc {
-            EMULATOR.write().unwrap().cycles(cpu, ppu, 2);
-            stack_push16(cpu, ppu, cpu.registers.pc);
+            bus.cycles(cpu, 1);
+            stack_push(cpu, bus, (cpu.registers.pc >> 8) as u8);
+            bus.cycles(cpu, 1);
+            stack_push(cpu, bus, cpu.registers.pc as u8);
         }
 
         cpu.registers.pc = address;
-        EMULATOR.write().unwrap().cycles(cpu, ppu, 1);
+        bus.cycles(cpu, 1);
     }
 }
 
-pub const PROCESSORS: [&dyn Fn(&mut CPUContext, &mut PPUContext) -> (); 36] = [
+type Processor = dyn Fn(&mut CPUContext, &mut dyn Bus);
+
+pub const PROCESSORS: [&Processor; 36] = [
     &proc_none,
     &proc_nop,
     &proc_ld,
@@ -575,7 +584,7 @@ pub const PROCESSORS: [&dyn Fn(&mut CPUContext, &mut PPUContext) -> (); 36] = [
     &proc_rst,
 ];
 
-pub fn proc_by_inst(inst_type: InstType) -> &'static dyn Fn(&mut CPUContext, &mut PPUContext) -> () {
+pub fn proc_by_inst(inst_type: InstType) -> &'static Processor {
     PROCESSORS[inst_type as usize]
 }
 
@@ -1,9 +1,7 @@
-use crate::comps::{cpu::CPUContext, instructions::RegType, bus::{bus_read, bus_write}};
-
-use super::ppu::PPUContext;
+use crate::comps::{cpu::CPUContext, instructions::RegType, bus::Bus};
 
 impl CPUContext {
-    pub fn read_reg8(&mut self, ppu: &PPUContext, rt: RegType) -> u8 {
+    pub fn read_reg8(&mut self, bus: &mut dyn Bus, rt: RegType) -> u8 {
         type RT = RegType;
         match rt {
             RT::A => self.registers.a,
@@ -16,7 +14,7 @@ impl CPUContext {
             RT::L => self.registers.l,
             RT::HL => {
                 let address = self.read_reg(Some(RT::HL));
-                bus_read(self, ppu, address)
+                bus.read(self, address)
             },
             _ => panic!("INVALID REG8: {rt:?}")
         }
@@ -26,26 +24,26 @@ impl CPUContext {
         type RT = RegType;
         match rt.unwrap() {
             RT::NONE => panic!("UNKNOWN REGISTER TYPE"),
-            RT::A => return self.registers.a as u16,
-            RT::F => return self.registers.f as u16,
-            RT::B => return self.registers.b as u16,
-            RT::C => return self.registers.c as u16,
-            RT::D => return self.registers.d as u16,
-            RT::E => return self.registers.e as u16,
-            RT::H => return self.registers.h as u16,
-            RT::L => return self.registers.l as u16,
+            RT::A => self.registers.a as u16,
+            RT::F => self.registers.f as u16,
+            RT::B => self.registers.b as u16,
+            RT::C => self.registers.c as u16,
+            RT::D => self.registers.d as u16,
+            RT::E => self.registers.e as u16,
+            RT::H => self.registers.h as u16,
+            RT::L => self.registers.l as u16,
     
-            RT::AF => return ((self.registers.a as u16) << 8) | self.registers.f as u16,
-            RT::BC => return ((self.registers.b as u16) << 8) | self.registers.c as u16,
-            RT::DE => return ((self.registers.d as u16) << 8) | self.registers.e as u16,
-            RT::HL => return ((self.registers.h as u16) << 8) | self.registers.l as u16,
+            RT::AF => ((self.registers.a as u16) << 8) | self.registers.f as u16,
+            RT::BC => ((self.registers.b as u16) << 8) | self.registers.c as u16,
+            RT::DE => ((self.registers.d as u16) << 8) | self.registers.e as u16,
+            RT::HL => ((self.registers.h as u16) << 8) | self.registers.l as u16,
     
-            RT::PC => return self.registers.pc,
-            RT::SP => return self.registers.sp
+            RT::PC => self.registers.pc,
+            RT::SP => self.registers.sp
         }
     }
     
-    pub fn set_reg8(&mut self, ppu: &mut PPUContext, rt: RegType, val: u8) {
+    pub fn set_reg8(&mut self, bus: &mut dyn Bus, rt: RegType, val: u8) {
         type RT = RegType;
         match rt {
             RT::A => self.registers.a = val,
@@ -58,7 +56,7 @@ impl CPUContext {
             RT::L => self.registers.l = val,
             RT::HL => {
                 let address = self.read_reg(Some(RT::HL));
-                bus_write(self, ppu, address, val);
+                bus.write(self, address, val);
             },
             _ => panic!("INVALID REG8: {rt:?}")
         }
@@ -103,6 +101,6 @@ impl CPUContext {
     }
     
     pub fn set_int_flags(&mut self, value: u8) {
-        self.int_flags = value;
+        self.int_flags = value & 0x1F; // Only the five interrupt bits exist
     }
 }
@@ -0,0 +1,326 @@
+// Command-line debugger, commands are run on the emulation thread between CPU steps
+
+use std::{collections::VecDeque, sync::RwLock};
+
+use super::{
+    breakpoints::{break_command, pc_check, take_hit, watch_command, without_watchpoints, BREAKPOINTS},
+    bus::{bus_read, MemoryMap},
+    common::parse_hex,
+    cpu::CPUContext,
+    disasm::{disassemble, inst_length},
+    emu::EMULATOR,
+    instructions::{inst_by_opcode, InstType},
+    ppu::PPUContext,
+    symbols::{parse_address, symbol_at},
+    trace::{trace_close, trace_open, tracing, TraceFormat},
+};
+
+const HISTORY_SIZE: usize = 8;
+
+pub const DEBUGGER_HELP: &str = "\
+Commands:
+    c, continue                 Resume emulation
+    p, pause                    Stop emulation
+    s, step [N]                 Execute N instructions (default: 1)
+    n, next                     Step over CALL and RST
+    u, until <ADDR>             Run until PC reaches ADDR
+    r, regs                     Print the registers and flags
+    x, mem <ADDR> [LEN]         Dump LEN bytes starting at ADDR (default: 64)
+    d, dis [ADDR] [N]           Disassemble N instructions from ADDR (default: around PC)
+    b, break [BANK:]ADDR [if COND]
+                                Stop before executing ADDR, optionally only in ROM bank BANK
+    b, break int [NAME] [if COND]
+                                Stop when an interrupt (vblank, stat, timer, serial, joypad) is dispatched
+    w, watch [r|w|rw] ADDR[-END] [if COND]
+                                Stop after an instruction reads or writes the range (default: rw)
+    bl, breakpoints             List breakpoints and watchpoints
+    t, trace <FILE> [FORMAT]    Write an instruction trace to FILE, full or doctor (default: full)
+    t, trace off                Stop tracing
+    del, delete [ID]            Delete a breakpoint, or all of them
+    q, quit                     Quit the emulator
+    h, help                     Print this help message
+
+Addresses and numbers are hexadecimal, optionally prefixed with $ or 0x. Addresses can also be
+labels from a .sym file next to the ROM, e.g. `b Main.loop` or `w wLives`. Labels win over numbers,
+so write $Dead for the address when there is also a label called Dead.
+Conditions use registers, numbers, [ADDR] for memory and == != < <= > >= + - & | ! && ||,
+e.g. `b 0150 if A == 0x3F && [HL] != 0`.
+";
+
+// Where a run started by `next` or `until` stops
+pub struct RunTo {
+    pub pc: u16,
+    pub sp: Option<u16>, // Only stop once SP is back at (or above) this, so recursive calls don't stop early
+}
+
+pub struct DebuggerContext {
+    pub enabled: bool,
+    pub run_to: Option<RunTo>,
+    pub history: VecDeque<u16>, // PCs of the last executed instructions, newest last
+    pub resuming: bool,         // Skips the breakpoint at PC once, so continuing from a breakpoint doesn't stop right away
+}
+
+pub static DEBUGGER: RwLock<DebuggerContext> = RwLock::new(DebuggerContext {
+    enabled: false,
+    run_to: None,
+    history: VecDeque::new(),
+    resuming: false,
+});
+
+pub fn debugger_enabled() -> bool {
+    DEBUGGER.read().unwrap().enabled
+}
+
+// Called before every step while the debugger is enabled, returns true if the core should stop
+pub fn debugger_before_step(cpu: &CPUContext, ppu: &PPUContext) -> bool {
+    let mut dbg = DEBUGGER.write().unwrap();
+    let pc = cpu.registers.pc;
+
+    if !std::mem::take(&mut dbg.resuming) {
+        if let Some(run_to) = &dbg.run_to {
+            if run_to.pc == pc && run_to.sp.is_none_or(|sp| sp <= cpu.registers.sp) {
+                dbg.run_to = None;
+                drop(dbg);
+
+                println!("Stopped at {}", disassemble_line(cpu, ppu, pc).0);
+                return true;
+            }
+        }
+
+        if let Some(hit) = pc_check(cpu, ppu) {
+            dbg.run_to = None;
+            drop(dbg);
+
+            println!("{hit}\nStopped at {}", disassemble_line(cpu, ppu, pc).0);
+            return true;
+        }
+    }
+
+    if !cpu.halted {
+        if dbg.history.len() == HISTORY_SIZE {
+            dbg.history.pop_front();
+        }
+        dbg.history.push_back(pc);
+    }
+
+    false
+}
+
+// Called after every step while the debugger is enabled, returns true if a watchpoint or interrupt breakpoint was hit
+pub fn debugger_after_step(cpu: &CPUContext, ppu: &PPUContext) -> bool {
+    match take_hit() {
+        Some(hit) => {
+            DEBUGGER.write().unwrap().run_to = None;
+            println!("{hit}\nStopped at {}", disassemble_line(cpu, ppu, cpu.registers.pc).0);
+            true
+        }
+        None => false,
+    }
+}
+
+// Runs a single command, cpu.stepping is set while the core is stopped
+pub fn debugger_command(cpu: &mut CPUContext, ppu: &mut PPUContext, line: &str) -> Result<String, String> {
+    let mut args = line.split_whitespace();
+    let command = args.next().unwrap_or("");
+    let args = args.collect::<Vec<&str>>();
+
+    match command {
+        "" => Ok(String::new()),
+        "h" | "help" => Ok(String::from(DEBUGGER_HELP)),
+        "c" | "continue" => {
+            DEBUGGER.write().unwrap().resuming = true;
+            cpu.stepping = false;
+            Ok(String::new())
+        }
+        "p" | "pause" => {
+            cpu.stepping = true;
+            DEBUGGER.write().unwrap().run_to = None;
+            Ok(format!("Stopped at {}", disassemble_line(cpu, ppu, cpu.registers.pc).0))
+        }
+        "s" | "step" => {
+            let count = match args.first() {
+                Some(arg) => number(arg)?,
+                None => 1,
+            };
+
+            cpu.stepping = true;
+            DEBUGGER.write().unwrap().resuming = true;
+
+            for _ in 0..count {
+                if debugger_before_step(cpu, ppu) {
+                    return Ok(String::new());
+                }
+
+                cpu.step(&mut MemoryMap { ppu });
+
+                if debugger_after_step(cpu, ppu) {
+                    return Ok(String::new());
+                }
+            }
+
+            Ok(disassemble_line(cpu, ppu, cpu.registers.pc).0)
+        }
+        "n" | "next" => {
+            let pc = cpu.registers.pc;
+            let inst = inst_by_opcode(without_watchpoints(|| bus_read(cpu, ppu, pc)));
+
+            if matches!(inst.inst_type, InstType::CALL | InstType::RST) {
+                let run_to = RunTo { pc: pc.wrapping_add(inst_length(inst) as u16), sp: Some(cpu.registers.sp) };
+                Ok(run(cpu, ppu, run_to))
+            } else {
+                debugger_command(cpu, ppu, "step")
+            }
+        }
+        "u" | "until" => {
+            let pc = parse_address(args.first().ok_or("until expects an address")?)?;
+            Ok(run(cpu, ppu, RunTo { pc, sp: None }))
+        }
+        "q" | "quit" => {
+            let mut emu = EMULATOR.write().unwrap();
+            emu.running = false;
+            emu.die = true;
+
+            Ok(String::new())
+        }
+        "b" | "break" => break_command(&args.join(" ")),
+        "w" | "watch" => watch_command(&args.join(" ")),
+        "bl" | "breakpoints" => Ok(BREAKPOINTS.read().unwrap().describe()),
+        "del" | "delete" => {
+            let id = match args.first() {
+                Some(arg) => Some(arg.parse().map_err(|_| format!("Invalid breakpoint: {arg}"))?),
+                None => None,
+            };
+
+            BREAKPOINTS.write().unwrap().delete(id)
+        }
+        "t" | "trace" => match args.as_slice() {
+            ["off"] => {
+                let was_tracing = tracing().is_some();
+                trace_close();
+                Ok(String::from(if was_tracing { "Stopped tracing" } else { "Not tracing" }))
+            }
+            [path, rest @ ..] if rest.len() <= 1 => {
+                let format = match rest.first() {
+                    Some(format) => format.parse()?,
+                    None => TraceFormat::Full,
+                };
+
+                trace_open(path, format).map_err(|e| format!("Couldn't create {path}: {e}"))?;
+                Ok(format!("Tracing to {path}"))
+            }
+            _ => Err(String::from("trace expects a file and an optional format, or off")),
+        },
+        "r" | "regs" => Ok(registers_string(cpu)),
+        "x" | "mem" => {
+            let start = parse_address(args.first().ok_or("mem expects an address")?)?;
+            let len = match args.get(1) {
+                Some(arg) => number(arg)?,
+                None => 64,
+            };
+
+            Ok(dump_memory(cpu, ppu, start, len))
+        }
+        "d" | "dis" => {
+            let mut lines = Vec::new();
+
+            let (mut address, count) = match args.first() {
+                Some(arg) => (parse_address(arg)?, 0),
+                None => {
+                    // Show the instructions that led here, then continue from PC
+                    let history = DEBUGGER.read().unwrap().history.iter().copied().collect::<Vec<u16>>();
+                    for pc in history.iter().filter(|pc| **pc != cpu.registers.pc) {
+                        lines.push(format!("   {}", disassemble_line(cpu, ppu, *pc).0));
+                    }
+
+                    (cpu.registers.pc, lines.len())
+                }
+            };
+
+            let total = match args.get(1) {
+                Some(arg) => number(arg)? as usize,
+                None => count + HISTORY_SIZE,
+            };
+
+            while lines.len() < total {
+                let (line, len) = disassemble_line(cpu, ppu, address);
+                let marker = if address == cpu.registers.pc { "-> " } else { "   " };
+                lines.push(format!("{marker}{line}"));
+                address = address.wrapping_add(len);
+            }
+
+            Ok(lines.join("\n"))
+        }
+        command => Err(format!("Unknown command: {command} (try help)")),
+    }
+}
+
+// Executes the current instruction, then lets the core run until it reaches run_to
+fn run(cpu: &mut CPUContext, ppu: &mut PPUContext, run_to: RunTo) -> String {
+    DEBUGGER.write().unwrap().resuming = true;
+    debugger_before_step(cpu, ppu);
+    cpu.step(&mut MemoryMap { ppu });
+
+    if debugger_after_step(cpu, ppu) {
+        return String::new();
+    }
+
+    if run_to.pc == cpu.registers.pc && run_to.sp.is_none_or(|sp| sp <= cpu.registers.sp) {
+        return disassemble_line(cpu, ppu, cpu.registers.pc).0;
+    }
+
+    DEBUGGER.write().unwrap().run_to = Some(run_to);
+    cpu.stepping = false;
+
+    String::new()
+}
+
+pub fn number(arg: &str) -> Result<u16, String> {
+    parse_hex(arg).ok_or(format!("Invalid number: {arg}"))
+}
+
+pub fn registers_string(cpu: &CPUContext) -> String {
+    let regs = &cpu.registers;
+    let flag = |set: bool, name: char| if set { name } else { '-' };
+
+    format!("A: {:02X} F: {:02X} B: {:02X} C: {:02X} D: {:02X} E: {:02X} H: {:02X} L: {:02X} SP: {:04X} PC: {:04X}\nFlags: {}{}{}{} IME: {} IE: {:02X} IF: {:02X}{}",
+        regs.a, regs.f, regs.b, regs.c, regs.d, regs.e, regs.h, regs.l, regs.sp, regs.pc,
+        flag(cpu.flag_z(), 'Z'), flag(cpu.flag_n(), 'N'), flag(cpu.flag_h(), 'H'), flag(cpu.flag_c(), 'C'),
+        cpu.int_master_enabled as u8,
+        cpu.ie_register,
+        cpu.int_flags,
+        if cpu.halted { " (halted)" } else { "" },
+    )
+}
+
+fn dump_memory(cpu: &CPUContext, ppu: &PPUContext, start: u16, len: u16) -> String {
+    without_watchpoints(|| dump_lines(cpu, ppu, start, len))
+}
+
+fn dump_lines(cpu: &CPUContext, ppu: &PPUContext, start: u16, len: u16) -> String {
+    let mut lines = Vec::new();
+
+    for row in (0..len as u32).step_by(16) {
+        let address = start.wrapping_add(row as u16);
+        let bytes = (row..(row + 16).min(len as u32))
+            .map(|i| bus_read(cpu, ppu, start.wrapping_add(i as u16)))
+            .collect::<Vec<u8>>();
+
+        let hex = bytes.iter().map(|byte| format!("{byte:02X}")).collect::<Vec<String>>().join(" ");
+        let ascii = bytes.iter().map(|byte| if byte.is_ascii_graphic() { *byte as char } else { '.' }).collect::<String>();
+        lines.push(format!("${address:04X}: {hex:47}  {ascii}"));
+    }
+
+    lines.join("\n")
+}
+
+// Decodes the instruction at address, returns the line and the instruction's length
+pub fn disassemble_line(cpu: &CPUContext, ppu: &PPUContext, address: u16) -> (String, u16) {
+    let bytes = without_watchpoints(|| (0..3).map(|i| bus_read(cpu, ppu, address.wrapping_add(i))).collect::<Vec<u8>>());
+    let (text, len) = disassemble(&bytes, address);
+
+    let hex = bytes[..len].iter().map(|byte| format!("{byte:02X}")).collect::<Vec<String>>().join(" ");
+    match symbol_at(address) {
+        Some(symbol) => (format!("${address:04X}: {hex:8}  {text:20} ; {symbol}"), len as u16),
+        None => (format!("${address:04X}: {hex:8}  {text}"), len as u16),
+    }
+}
@@ -0,0 +1,154 @@
+// SM83 disassembler, decodes from raw bytes without needing a CPU
+
+use super::{
+    cpu_proc::decode_reg,
+    instructions::{inst_by_opcode, AddrMode, CondType, InstType, Instruction, RegType},
+};
+
+const CB_OPS: [InstType; 8] = [
+    InstType::RLC,
+    InstType::RRC,
+    InstType::RL,
+    InstType::RR,
+    InstType::SLA,
+    InstType::SRA,
+    InstType::SWAP,
+    InstType::SRL,
+];
+
+// Bytes taken by an instruction, including the opcode
+pub fn inst_length(inst: &Instruction) -> usize {
+    type AM = AddrMode;
+
+    match inst.mode {
+        // STOP is followed by a padding byte, usually 00
+        _ if matches!(inst.inst_type, InstType::STOP) => 2,
+        AM::RxD16 | AM::RxA16 | AM::D16 | AM::A16xR | AM::D16xR => 3,
+        AM::RxD8 | AM::RxA8 | AM::A8xR | AM::HLxSPR | AM::D8 | AM::MRxD8 => 2,
+        _ => 1,
+    }
+}
+
+// Decodes the instruction at the start of bytes, which is located at address.
+// Returns the instruction and its length, bytes that don't form an instruction are shown as DB
+pub fn disassemble(bytes: &[u8], address: u16) -> (String, usize) {
+    let Some(&opcode) = bytes.first() else {
+        return (String::new(), 0);
+    };
+
+    let inst = inst_by_opcode(opcode);
+    let len = inst_length(inst);
+
+    if matches!(inst.inst_type, InstType::NONE) || bytes.len() < len {
+        return (format!("DB ${opcode:02X}"), 1);
+    }
+
+    if let InstType::CB = inst.inst_type {
+        return (cb_string(bytes[1]), 2);
+    }
+
+    let d8 = bytes.get(1).copied().unwrap_or(0);
+    let d16 = (bytes.get(2).copied().unwrap_or(0) as u16) << 8 | d8 as u16;
+
+    (inst_string(inst, d8, d16, address.wrapping_add(len as u16)), len)
+}
+
+fn cb_string(op: u8) -> String {
+    let reg = cb_reg(decode_reg(op & 0b111));
+    let bit = (op >> 3) & 0b111;
+
+    match op >> 6 {
+        0 => format!("{} {reg}", CB_OPS[bit as usize]),
+        1 => format!("BIT {bit},{reg}"),
+        2 => format!("RES {bit},{reg}"),
+        _ => format!("SET {bit},{reg}"),
+    }
+}
+
+// Index 6 of the CB register lookup is (HL)
+fn cb_reg(reg: RegType) -> String {
+    match reg {
+        RegType::HL => String::from("(HL)"),
+        reg => format!("{reg}"),
+    }
+}
+
+fn cond_string(cond: &CondType) -> Option<&'static str> {
+    match cond {
+        CondType::NONE => None,
+        CondType::NZ => Some("NZ"),
+        CondType::Z => Some("Z"),
+        CondType::NC => Some("NC"),
+        CondType::C => Some("C"),
+    }
+}
+
+fn signed(value: u8) -> String {
+    match value as i8 {
+        value if value < 0 => format!("-${:02X}", value.unsigned_abs()),
+        value => format!("+${value:02X}"),
+    }
+}
+
+// next is the address of the following instruction, JR offsets are relative to it
+fn inst_string(inst: &Instruction, d8: u8, d16: u16, next: u16) -> String {
+    type AM = AddrMode;
+    type IN = InstType;
+
+    let r1 = || inst.reg1.unwrap();
+    let r2 = || inst.reg2.unwrap();
+    let cond = cond_string(&inst.cond);
+
+    let operands = match inst.mode {
+        AM::IMP => match (inst.inst_type, inst.param) {
+            (IN::RST, Some(vector)) => format!("${vector:02X}"),
+            _ => cond.unwrap_or("").to_string(),
+        },
+        AM::RxD16 => format!("{},${d16:04X}", r1()),
+        AM::RxR => format!("{},{}", r1(), r2()),
+        AM::MRxR => format!("({}),{}", r1(), r2()),
+        AM::R => format!("{}", r1()),
+        AM::RxD8 => match r1() {
+            RegType::SP => format!("SP,{}", signed(d8)),
+            reg => format!("{reg},${d8:02X}"),
+        },
+        AM::RxMR => format!("{},({})", r1(), r2()),
+        AM::RxHLI => format!("{},(HL+)", r1()),
+        AM::RxHLD => format!("{},(HL-)", r1()),
+        AM::HLIxR => format!("(HL+),{}", r2()),
+        AM::HLDxR => format!("(HL-),{}", r2()),
+        AM::RxA8 => format!("{},($FF{d8:02X})", r1()),
+        AM::A8xR => format!("($FF{d8:02X}),{}", r2()),
+        AM::HLxSPR => format!("HL,SP{}", signed(d8)),
+        AM::D16 => match cond {
+            Some(cond) => format!("{cond},${d16:04X}"),
+            None => format!("${d16:04X}"),
+        },
+        AM::D8 => {
+            // JR, the only instruction using this mode besides the CB prefix
+            let target = next.wrapping_add(d8 as i8 as u16);
+            match cond {
+                Some(cond) => format!("{cond},${target:04X}"),
+                None => format!("${target:04X}"),
+            }
+        }
+        AM::D16xR => format!("${d16:04X},{}", r2()),
+        AM::MRxD8 => format!("({}),${d8:02X}", r1()),
+        AM::MR => format!("({})", r1()),
+        AM::A16xR => format!("(${d16:04X}),{}", r2()),
+        AM::RxA16 => format!("{},(${d16:04X})", r1()),
+    };
+
+    // The table isn't consistent about A in 8-bit arithmetic, always shown as ADD A,B and ADC A,B and SBC A,B but
+    // SUB B, AND B, XOR B, OR B and CP B
+    let operands = match inst.inst_type {
+        IN::SUB | IN::AND | IN::XOR | IN::OR | IN::CP => operands.strip_prefix("A,").unwrap_or(&operands).to_string(),
+        _ => operands,
+    };
+
+    if operands.is_empty() {
+        format!("{}", inst.inst_type)
+    } else {
+        format!("{} {operands}", inst.inst_type)
+    }
+}
@@ -36,8 +36,13 @@ impl DMAContext {
             return;
         }
 
-        // NOTICE: Should check
-        let value = bus_read(cpu, ppu, self.value as u16 * 0x100 + self.byte as u16);
+        // Sources from 0xE000 up read WRAM, including 0xFE00 - 0xFFFF, which the DMA can't reach otherwise
+        let mut address = self.value as u16 * 0x100 + self.byte as u16;
+        if address >= 0xE000 {
+            address -= 0x2000;
+        }
+
+        let value = bus_read(cpu, ppu, address);
         ppu.oam_write(self.byte as u16, value);
         self.byte += 1;
 
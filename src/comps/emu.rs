@@ -1,6 +1,6 @@
 use std::sync::RwLock;
 
-use super::{timer::timer_tick, cpu::CPUContext, dma::DMA, ppu::{PPU, PPUContext}};
+use super::{timer::timer_tick, serial::serial_tick, cpu::CPUContext, dma::DMA, ppu::PPUContext};
 
 /*
     Emu components:
@@ -21,6 +21,7 @@ use super::{timer::timer_tick, cpu::CPUContext, dma::DMA, ppu::{PPU, PPUContext}
 pub struct EmulatorContext {
     pub running: bool,
     pub paused: bool,
+    pub rewinding: bool, // While the rewind key is held
     pub die: bool,
     pub ticks: u64,
 }
@@ -28,6 +29,7 @@ pub struct EmulatorContext {
 pub static EMULATOR: RwLock<EmulatorContext> = RwLock::new(EmulatorContext {
     running: true,
     paused: false,
+    rewinding: false,
     die: false,
     ticks: 0,
 });
@@ -42,6 +44,7 @@ impl EmulatorContext {
             }
 
             DMA.write().unwrap().tick(cpu, ppu);
+            serial_tick(cpu);
         }
     }
 }
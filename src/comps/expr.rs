@@ -0,0 +1,234 @@
+// Condition expressions for breakpoints, e.g. `A == 0x3F && [HL] != 0`
+
+use super::{bus::bus_read, common::parse_hex, cpu::CPUContext, instructions::RegType, ppu::PPUContext};
+
+/*
+    Grammar, from lowest to highest precedence:
+
+    |or|      -> and ("||" and)*
+    |and|     -> compare ("&&" compare)*
+    |compare| -> sum (("==" | "!=" | "<" | "<=" | ">" | ">=") sum)?
+    |sum|     -> unary (("+" | "-" | "&" | "|") unary)*
+    |unary|   -> "!" unary | primary
+    |primary| -> NUMBER | REGISTER | "[" or "]" | "(" or ")"
+
+    Numbers are hexadecimal like everywhere else in the debugger, and must start with a digit, $ or 0x
+    so they can't be mistaken for registers. [X] reads the byte at address X.
+*/
+pub enum Expr {
+    Number(u16),
+    Register(RegType),
+    Memory(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+}
+
+// Longer operators first, so "<=" isn't read as "<"
+const SYMBOLS: [&str; 17] = ["||", "&&", "==", "!=", "<=", ">=", "<", ">", "+", "-", "&", "|", "!", "[", "]", "(", ")"];
+
+enum Token {
+    Number(u16),
+    Name(String),
+    Symbol(&'static str),
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+
+    while !rest.is_empty() {
+        if let Some(op) = SYMBOLS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Symbol(op));
+            rest = &rest[op.len()..];
+        } else {
+            let len = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '$').unwrap_or(rest.len());
+            if len == 0 {
+                return Err(format!("Unexpected character in condition: {}", rest.chars().next().unwrap()));
+            }
+
+            let word = &rest[..len];
+            if word.starts_with(|c: char| c.is_ascii_digit() || c == '$') {
+                let number = parse_hex(word).ok_or(format!("Invalid number: {word}"))?;
+                tokens.push(Token::Number(number));
+            } else {
+                tokens.push(Token::Name(word.to_uppercase()));
+            }
+
+            rest = &rest[len..];
+        }
+
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn eat(&mut self, symbols: &[&'static str]) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Symbol(symbol)) if symbols.contains(symbol) => {
+                self.pos += 1;
+                Some(symbol)
+            }
+            _ => None,
+        }
+    }
+
+    fn binary(&mut self, symbols: &[&'static str], next: fn(&mut Parser) -> Result<Expr, String>) -> Result<Expr, String> {
+        let mut lhs = next(self)?;
+
+        while let Some(op) = self.eat(symbols) {
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(next(self)?));
+        }
+
+        Ok(lhs)
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        self.binary(&["||"], Parser::and)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        self.binary(&["&&"], Parser::compare)
+    }
+
+    fn compare(&mut self) -> Result<Expr, String> {
+        let lhs = self.sum()?;
+
+        match self.eat(&["==", "!=", "<=", ">=", "<", ">"]) {
+            Some(op) => Ok(Expr::Binary(op, Box::new(lhs), Box::new(self.sum()?))),
+            None => Ok(lhs),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        self.binary(&["+", "-", "&", "|"], Parser::unary)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat(&["!"]).is_some() {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        if self.eat(&["["]).is_some() {
+            let address = self.or()?;
+            self.eat(&["]"]).ok_or("Expected ] in condition")?;
+            return Ok(Expr::Memory(Box::new(address)));
+        }
+
+        if self.eat(&["("]).is_some() {
+            let expr = self.or()?;
+            self.eat(&[")"]).ok_or("Expected ) in condition")?;
+            return Ok(expr);
+        }
+
+        type RT = RegType;
+        let expr = match self.tokens.get(self.pos) {
+            Some(Token::Number(number)) => Expr::Number(*number),
+            Some(Token::Name(name)) => Expr::Register(match name.as_str() {
+                "A" => RT::A,
+                "F" => RT::F,
+                "B" => RT::B,
+                "C" => RT::C,
+                "D" => RT::D,
+                "E" => RT::E,
+                "H" => RT::H,
+                "L" => RT::L,
+                "AF" => RT::AF,
+                "BC" => RT::BC,
+                "DE" => RT::DE,
+                "HL" => RT::HL,
+                "SP" => RT::SP,
+                "PC" => RT::PC,
+                name => return Err(format!("Unknown register in condition: {name}")),
+            }),
+            Some(Token::Symbol(symbol)) => return Err(format!("Unexpected {symbol} in condition")),
+            None => return Err(String::from("Condition ends unexpectedly")),
+        };
+
+        self.pos += 1;
+        Ok(expr)
+    }
+}
+
+pub fn parse_expr(source: &str) -> Result<Expr, String> {
+    let mut parser = Parser { tokens: tokenize(source)?, pos: 0 };
+    let expr = parser.or()?;
+
+    if parser.pos < parser.tokens.len() {
+        return Err(format!("Unexpected trailing input in condition: {source}"));
+    }
+
+    Ok(expr)
+}
+
+impl Expr {
+    // Comparisons and logical operators give 1 or 0, a condition holds when the result isn't 0
+    pub fn eval(&self, cpu: &CPUContext, ppu: &PPUContext) -> u16 {
+        match self {
+            Expr::Number(number) => *number,
+            Expr::Register(reg) => register(cpu, *reg),
+            Expr::Memory(address) => bus_read(cpu, ppu, address.eval(cpu, ppu)) as u16,
+            Expr::Not(expr) => (expr.eval(cpu, ppu) == 0) as u16,
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = lhs.eval(cpu, ppu);
+
+                // Short-circuit, so [X] on the right isn't read needlessly
+                match *op {
+                    "&&" if lhs == 0 => return 0,
+                    "||" if lhs != 0 => return 1,
+                    _ => {}
+                }
+
+                let rhs = rhs.eval(cpu, ppu);
+                match *op {
+                    "||" | "&&" => (rhs != 0) as u16,
+                    "==" => (lhs == rhs) as u16,
+                    "!=" => (lhs != rhs) as u16,
+                    "<" => (lhs < rhs) as u16,
+                    "<=" => (lhs <= rhs) as u16,
+                    ">" => (lhs > rhs) as u16,
+                    ">=" => (lhs >= rhs) as u16,
+                    "+" => lhs.wrapping_add(rhs),
+                    "-" => lhs.wrapping_sub(rhs),
+                    "&" => lhs & rhs,
+                    "|" => lhs | rhs,
+                    op => unreachable!("Unknown operator {op}"),
+                }
+            }
+        }
+    }
+}
+
+fn register(cpu: &CPUContext, reg: RegType) -> u16 {
+    type RT = RegType;
+    let regs = &cpu.registers;
+    let pair = |hi: u8, lo: u8| (hi as u16) << 8 | lo as u16;
+
+    match reg {
+        RT::A => regs.a as u16,
+        RT::F => regs.f as u16,
+        RT::B => regs.b as u16,
+        RT::C => regs.c as u16,
+        RT::D => regs.d as u16,
+        RT::E => regs.e as u16,
+        RT::H => regs.h as u16,
+        RT::L => regs.l as u16,
+        RT::AF => pair(regs.a, regs.f),
+        RT::BC => pair(regs.b, regs.c),
+        RT::DE => pair(regs.d, regs.e),
+        RT::HL => pair(regs.h, regs.l),
+        RT::SP => regs.sp,
+        RT::PC => regs.pc,
+        RT::NONE => 0,
+    }
+}
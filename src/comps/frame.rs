@@ -0,0 +1,75 @@
+// Frame presentation between the emulation thread and the UI thread
+
+use std::sync::{atomic::{AtomicU8, Ordering}, Mutex};
+
+use super::ppu::{X_RES, Y_RES};
+
+pub const FRAME_SIZE: usize = Y_RES as usize * X_RES as usize;
+
+const INDEX_MASK: u8 = 0b011;
+const FRESH: u8 = 0b100; // Set when the middle buffer holds a frame the UI hasn't seen yet
+
+pub struct Frame {
+    pub number: u32,
+    pub pixels: [u32; FRAME_SIZE],
+    pub vram: [u8; 0x2000], // Snapshot for the tile viewer
+}
+
+/*
+    Triple buffering:
+
+    |Back|   <- Written by the PPU, only ever touched by the emulation thread
+    |Middle| <- Last completed frame, swapped atomically by both sides
+    |Front|  <- Read by the UI, only ever touched by the UI thread
+
+    Since each side only locks the buffer it owns, the mutexes are never contended
+    and neither thread waits on the other.
+*/
+pub struct FrameBuffers {
+    buffers: [Mutex<Frame>; 3],
+    middle: AtomicU8,
+    back: AtomicU8,
+    front: AtomicU8,
+}
+
+pub static FRAMES: FrameBuffers = FrameBuffers {
+    buffers: [
+        Mutex::new(Frame { number: 0, pixels: [0; FRAME_SIZE], vram: [0; 0x2000] }),
+        Mutex::new(Frame { number: 0, pixels: [0; FRAME_SIZE], vram: [0; 0x2000] }),
+        Mutex::new(Frame { number: 0, pixels: [0; FRAME_SIZE], vram: [0; 0x2000] }),
+    ],
+    middle: AtomicU8::new(1),
+    back: AtomicU8::new(0),
+    front: AtomicU8::new(2),
+};
+
+impl FrameBuffers {
+    // Called by the PPU once a frame is complete (single producer)
+    pub fn publish(&self, number: u32, pixels: &[u32; FRAME_SIZE], vram: &[u8; 0x2000]) {
+        let back = self.back.load(Ordering::Relaxed);
+
+        {
+            let mut frame = self.buffers[back as usize].lock().unwrap();
+            frame.number = number;
+            frame.pixels.copy_from_slice(pixels);
+            frame.vram.copy_from_slice(vram);
+        }
+
+        let prev = self.middle.swap(back | FRESH, Ordering::AcqRel);
+        self.back.store(prev & INDEX_MASK, Ordering::Relaxed);
+    }
+
+    // Called by the UI (single consumer). Runs f on the newest frame if one arrived since the last call
+    pub fn latest<R>(&self, f: impl FnOnce(&Frame) -> R) -> Option<R> {
+        if self.middle.load(Ordering::Relaxed) & FRESH == 0 {
+            return None;
+        }
+
+        let front = self.front.load(Ordering::Relaxed);
+        let prev = self.middle.swap(front, Ordering::AcqRel);
+        self.front.store(prev & INDEX_MASK, Ordering::Relaxed);
+
+        let frame = self.buffers[(prev & INDEX_MASK) as usize].lock().unwrap();
+        Some(f(&frame))
+    }
+}
@@ -0,0 +1,419 @@
+// GDB remote serial protocol stub, so gdb, lldb or any other RSP client can debug the running ROM
+
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::RwLock,
+    time::Duration,
+};
+
+use super::{
+    breakpoints::{without_watchpoints, BreakKind, Breakpoint, BREAKPOINTS},
+    bus::{bus_read, bus_write, MemoryMap},
+    cpu::CPU,
+    debugger::{debugger_after_step, debugger_before_step, DEBUGGER},
+    emu::EMULATOR,
+    ppu::PPU,
+};
+
+/*
+    Registers, in the order of the g packet, 16 bits each and little endian:
+
+    |AF|BC|DE|HL|SP|PC|
+
+    The same layout is described by target.xml for clients that ask for it.
+*/
+const REGISTER_COUNT: usize = 6;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.gnu.gdb.sm83.core">
+    <reg name="af" bitsize="16" type="int" regnum="0"/>
+    <reg name="bc" bitsize="16" type="int"/>
+    <reg name="de" bitsize="16" type="int"/>
+    <reg name="hl" bitsize="16" type="int"/>
+    <reg name="sp" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>"#;
+
+// IDs of the breakpoints the client inserted, the only ones it removes. The debugger's own are left alone
+static INSERTED: RwLock<Vec<u32>> = RwLock::new(Vec::new());
+
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+
+// Serves one client after another until the emulator quits
+pub fn gdb_listen(listener: TcpListener) {
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+
+        if let Err(e) = gdb_serve(stream) {
+            eprintln!("GDB connection closed: {e}");
+        }
+
+        if !EMULATOR.read().unwrap().running {
+            break;
+        }
+    }
+}
+
+struct Connection {
+    stream: TcpStream,
+}
+
+impl Connection {
+    // Returns None when the read timed out, Some(0x03) when the client asked to interrupt
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+
+        match self.stream.read(&mut byte) {
+            Ok(0) => Err(io::Error::new(ErrorKind::UnexpectedEof, "client disconnected")),
+            Ok(_) => Ok(Some(byte[0])),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn wait_byte(&mut self) -> io::Result<u8> {
+        loop {
+            if let Some(byte) = self.read_byte()? {
+                return Ok(byte);
+            }
+        }
+    }
+
+    // $<data>#<checksum>, acknowledged with + or -
+    fn read_packet(&mut self) -> io::Result<String> {
+        loop {
+            if self.wait_byte()? != b'$' {
+                continue;
+            }
+
+            let mut data = Vec::new();
+            loop {
+                match self.wait_byte()? {
+                    b'#' => break,
+                    b'}' => data.push(self.wait_byte()? ^ 0x20),
+                    byte => data.push(byte),
+                }
+            }
+
+            let checksum = [self.wait_byte()?, self.wait_byte()?];
+            let expected = u8::from_str_radix(std::str::from_utf8(&checksum).unwrap_or(""), 16).ok();
+
+            if expected == Some(data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))) {
+                self.stream.write_all(b"+")?;
+                return Ok(String::from_utf8_lossy(&data).into_owned());
+            }
+
+            self.stream.write_all(b"-")?;
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let mut escaped = Vec::with_capacity(data.len());
+        for byte in data.bytes() {
+            match byte {
+                b'$' | b'#' | b'}' | b'*' => escaped.extend_from_slice(&[b'}', byte ^ 0x20]),
+                byte => escaped.push(byte),
+            }
+        }
+
+        let checksum = escaped.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+
+        loop {
+            self.stream.write_all(b"$")?;
+            self.stream.write_all(&escaped)?;
+            write!(self.stream, "#{checksum:02x}")?;
+
+            // Wait for the acknowledgement, resending on -
+            match self.wait_byte()? {
+                b'-' => continue,
+                _ => return Ok(()),
+            }
+        }
+    }
+}
+
+fn gdb_serve(stream: TcpStream) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    stream.set_read_timeout(Some(Duration::from_millis(10)))?;
+    println!("GDB client connected from {}", stream.peer_addr()?);
+
+    let mut conn = Connection { stream };
+
+    // The core stays stopped while the client is attached, until it continues or steps
+    CPU.write().unwrap().stepping = true;
+
+    loop {
+        let packet = conn.read_packet()?;
+
+        let reply = match packet.as_bytes().first() {
+            Some(b'c') => {
+                resume(&packet[1..]);
+                wait_for_stop(&mut conn)?
+            }
+            Some(b'k') => {
+                let mut emu = EMULATOR.write().unwrap();
+                emu.running = false;
+                emu.die = true;
+                return Ok(());
+            }
+            Some(b'D') => {
+                let inserted = std::mem::take(&mut *INSERTED.write().unwrap());
+                BREAKPOINTS.write().unwrap().remove_where(|b| inserted.contains(&b.id));
+                resume("");
+                conn.send("OK")?;
+                return Ok(());
+            }
+            _ => handle_packet(&packet),
+        };
+
+        conn.send(&reply)?;
+
+        if !EMULATOR.read().unwrap().running {
+            return Ok(());
+        }
+    }
+}
+
+fn resume(address: &str) {
+    let mut cpu = CPU.write().unwrap();
+
+    if let Ok(address) = u16::from_str_radix(address, 16) {
+        cpu.registers.pc = address;
+    }
+
+    DEBUGGER.write().unwrap().resuming = true;
+    cpu.stepping = false;
+}
+
+// Lets the core run until a breakpoint stops it or the client interrupts
+fn wait_for_stop(conn: &mut Connection) -> io::Result<String> {
+    loop {
+        if conn.read_byte()? == Some(0x03) {
+            CPU.write().unwrap().stepping = true;
+            DEBUGGER.write().unwrap().run_to = None;
+            return Ok(format!("S{SIGINT:02x}"));
+        }
+
+        if !EMULATOR.read().unwrap().running {
+            return Ok(String::from("W00"));
+        }
+
+        if CPU.read().unwrap().stepping {
+            return Ok(format!("S{SIGTRAP:02x}"));
+        }
+    }
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn parse_hex_bytes(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+// addr,len
+fn parse_range(args: &str) -> Option<(u16, u16)> {
+    let (address, len) = args.split_once(',')?;
+    Some((u16::from_str_radix(address, 16).ok()?, u16::from_str_radix(len, 16).ok()?))
+}
+
+// Packets that are answered right away, an empty reply means unsupported
+fn handle_packet(packet: &str) -> String {
+    let (command, args) = if packet.is_char_boundary(1) { packet.split_at(1) } else { ("", "") };
+
+    match command {
+        "?" => format!("S{SIGTRAP:02x}"),
+        "g" => {
+            let cpu = CPU.read().unwrap();
+            let regs = &cpu.registers;
+            let pair = |hi: u8, lo: u8| (hi as u16) << 8 | lo as u16;
+
+            [pair(regs.a, regs.f), pair(regs.b, regs.c), pair(regs.d, regs.e), pair(regs.h, regs.l), regs.sp, regs.pc]
+                .iter()
+                .map(|value| hex_bytes(&value.to_le_bytes()))
+                .collect()
+        }
+        "G" => match parse_hex_bytes(args) {
+            Some(bytes) if bytes.len() == REGISTER_COUNT * 2 => {
+                for (n, value) in bytes.chunks(2).enumerate() {
+                    set_register(n, u16::from_le_bytes([value[0], value[1]]));
+                }
+                String::from("OK")
+            }
+            _ => String::from("E01"),
+        },
+        "p" => match usize::from_str_radix(args, 16) {
+            Ok(n) if n < REGISTER_COUNT => {
+                let cpu = CPU.read().unwrap();
+                let regs = &cpu.registers;
+                let value = match n {
+                    0 => (regs.a as u16) << 8 | regs.f as u16,
+                    1 => (regs.b as u16) << 8 | regs.c as u16,
+                    2 => (regs.d as u16) << 8 | regs.e as u16,
+                    3 => (regs.h as u16) << 8 | regs.l as u16,
+                    4 => regs.sp,
+                    _ => regs.pc,
+                };
+                hex_bytes(&value.to_le_bytes())
+            }
+            _ => String::from("E01"),
+        },
+        "P" => {
+            let register = args.split_once('=').and_then(|(n, value)| {
+                let bytes = parse_hex_bytes(value)?;
+                Some((usize::from_str_radix(n, 16).ok()?, u16::from_le_bytes([*bytes.first()?, *bytes.get(1).unwrap_or(&0)])))
+            });
+
+            match register {
+                Some((n, value)) if n < REGISTER_COUNT => {
+                    set_register(n, value);
+                    String::from("OK")
+                }
+                _ => String::from("E01"),
+            }
+        }
+        "m" => match parse_range(args) {
+            Some((address, len)) => {
+                let cpu = CPU.read().unwrap();
+                let ppu = PPU.read().unwrap();
+                let bytes = without_watchpoints(|| {
+                    (0..len).map(|i| bus_read(&cpu, &ppu, address.wrapping_add(i))).collect::<Vec<u8>>()
+                });
+                hex_bytes(&bytes)
+            }
+            None => String::from("E01"),
+        },
+        "M" => {
+            let write = args.split_once(':').and_then(|(range, data)| Some((parse_range(range)?, parse_hex_bytes(data)?)));
+
+            match write {
+                Some(((address, len), bytes)) if bytes.len() == len as usize => {
+                    let mut cpu = CPU.write().unwrap();
+                    let mut ppu = PPU.write().unwrap();
+                    without_watchpoints(|| {
+                        for (i, byte) in bytes.iter().enumerate() {
+                            bus_write(&mut cpu, &mut ppu, address.wrapping_add(i as u16), *byte);
+                        }
+                    });
+                    String::from("OK")
+                }
+                _ => String::from("E01"),
+            }
+        }
+        "s" => {
+            let mut cpu = CPU.write().unwrap();
+            let mut ppu = PPU.write().unwrap();
+
+            if let Ok(address) = u16::from_str_radix(args, 16) {
+                cpu.registers.pc = address;
+            }
+
+            DEBUGGER.write().unwrap().resuming = true;
+            debugger_before_step(&cpu, &ppu);
+            cpu.step(&mut MemoryMap { ppu: &mut ppu });
+            debugger_after_step(&cpu, &ppu);
+
+            format!("S{SIGTRAP:02x}")
+        }
+        "Z" | "z" => match breakpoint_kind(args) {
+            Some(kind) => {
+                let mut breakpoints = BREAKPOINTS.write().unwrap();
+                let mut inserted = INSERTED.write().unwrap();
+                let same = |b: &Breakpoint| inserted.contains(&b.id) && same_kind(&b.kind, &kind);
+
+                // Only one breakpoint per location, gdb may insert the same one again
+                breakpoints.remove_where(same);
+                inserted.retain(|id| breakpoints.list.iter().any(|b| b.id == *id));
+
+                if command == "Z" {
+                    inserted.push(breakpoints.next_id);
+                    breakpoints.add(kind, None);
+                }
+
+                String::from("OK")
+            }
+            None => String::new(),
+        },
+        "H" | "T" => String::from("OK"),
+        "q" => query(args),
+        _ => String::new(),
+    }
+}
+
+// type,addr,kind where type is 0/1 for breakpoints and 2/3/4 for write/read/access watchpoints
+fn breakpoint_kind(args: &str) -> Option<BreakKind> {
+    let mut fields = args.split(',');
+    let kind = fields.next()?;
+    let address = u16::from_str_radix(fields.next()?, 16).ok()?;
+    let len = u16::from_str_radix(fields.next()?, 16).ok()?.max(1);
+    let end = address.wrapping_add(len - 1);
+
+    match kind {
+        "0" | "1" => Some(BreakKind::Pc { address, bank: None }),
+        "2" => Some(BreakKind::Watch { start: address, end, read: false, write: true }),
+        "3" => Some(BreakKind::Watch { start: address, end, read: true, write: false }),
+        "4" => Some(BreakKind::Watch { start: address, end, read: true, write: true }),
+        _ => None,
+    }
+}
+
+fn same_kind(a: &BreakKind, b: &BreakKind) -> bool {
+    match (a, b) {
+        (BreakKind::Pc { address: a, bank: None }, BreakKind::Pc { address: b, bank: None }) => a == b,
+        (
+            BreakKind::Watch { start: s1, end: e1, read: r1, write: w1 },
+            BreakKind::Watch { start: s2, end: e2, read: r2, write: w2 },
+        ) => (s1, e1, r1, w1) == (s2, e2, r2, w2),
+        _ => false,
+    }
+}
+
+fn set_register(n: usize, value: u16) {
+    let mut cpu = CPU.write().unwrap();
+    let regs = &mut cpu.registers;
+    let (hi, lo) = ((value >> 8) as u8, value as u8);
+
+    match n {
+        0 => (regs.a, regs.f) = (hi, lo & 0xF0),
+        1 => (regs.b, regs.c) = (hi, lo),
+        2 => (regs.d, regs.e) = (hi, lo),
+        3 => (regs.h, regs.l) = (hi, lo),
+        4 => regs.sp = value,
+        _ => regs.pc = value,
+    }
+}
+
+fn query(args: &str) -> String {
+    match args {
+        args if args.starts_with("Supported") => String::from("PacketSize=1000;qXfer:features:read+"),
+        "Attached" => String::from("1"),
+        "C" => String::from("QC1"),
+        "fThreadInfo" => String::from("m1"),
+        "sThreadInfo" => String::from("l"),
+        args if args.starts_with("Xfer:features:read:target.xml:") => {
+            let range = &args["Xfer:features:read:target.xml:".len()..];
+            let Some((offset, len)) = range.split_once(',') else { return String::from("E01") };
+            let (Ok(offset), Ok(len)) = (usize::from_str_radix(offset, 16), usize::from_str_radix(len, 16)) else {
+                return String::from("E01");
+            };
+
+            let xml = TARGET_XML.as_bytes();
+            let start = offset.min(xml.len());
+            let end = (start + len).min(xml.len());
+            let marker = if end == xml.len() { 'l' } else { 'm' };
+
+            format!("{marker}{}", String::from_utf8_lossy(&xml[start..end]))
+        }
+        _ => String::new(),
+    }
+}
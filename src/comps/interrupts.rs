@@ -1,6 +1,6 @@
 use crate::comps::{cpu::CPUContext, stack::stack_push16};
 
-use super::ppu::PPUContext;
+use super::bus::Bus;
 
 #[derive(Clone, Copy)]
 pub enum InterruptType {
@@ -11,12 +11,13 @@ pub enum InterruptType {
     Joypad  = 0b10000
 }
 
-fn int_check(cpu: &mut CPUContext, ppu: &mut PPUContext, address: u16, it: InterruptType) -> bool {
+fn int_check(cpu: &mut CPUContext, bus: &mut dyn Bus, address: u16, it: InterruptType) -> bool {
     if cpu.int_flags & it as u8 != 0 && cpu.get_ie_reg() & it as u8 != 0 {
-        int_handle(cpu, ppu, address);
+        int_handle(cpu, bus, address);
         cpu.int_flags &= !(it as u8);
         cpu.halted = false;
         cpu.int_master_enabled = false;
+        bus.interrupt_taken(cpu, it);
 
         return true;
     }
@@ -24,22 +25,16 @@ fn int_check(cpu: &mut CPUContext, ppu: &mut PPUContext, address: u16, it: Inter
     false
 }
 
-pub fn int_handle(cpu: &mut CPUContext, ppu: &mut PPUContext, address: u16) {
-    stack_push16(cpu, ppu, cpu.registers.pc);
+pub fn int_handle(cpu: &mut CPUContext, bus: &mut dyn Bus, address: u16) {
+    stack_push16(cpu, bus, cpu.registers.pc);
     cpu.registers.pc = address;
 }
 
-pub fn handle_interrupts(cpu: &mut CPUContext, ppu: &mut PPUContext) {
+pub fn handle_interrupts(cpu: &mut CPUContext, bus: &mut dyn Bus) {
     type IT = InterruptType;
-    if int_check(cpu, ppu, 0x40, IT::VBlank) {
-
-    } else if int_check(cpu, ppu, 0x48, IT::LCDStat) {
-
-    } else if int_check(cpu, ppu, 0x50, IT::Timer) {
-        
-    } else if int_check(cpu, ppu, 0x58, IT::Serial) {
-        
-    } else if int_check(cpu, ppu, 0x60, IT::Joypad) {
-        
-    }
+    let _ = int_check(cpu, bus, 0x40, IT::VBlank)
+        || int_check(cpu, bus, 0x48, IT::LCDStat)
+        || int_check(cpu, bus, 0x50, IT::Timer)
+        || int_check(cpu, bus, 0x58, IT::Serial)
+        || int_check(cpu, bus, 0x60, IT::Joypad);
 }
@@ -1,35 +1,60 @@
 use std::sync::RwLock;
 
 use super::{
+    apu::APU,
+    boot::BOOT,
     common::between,
     cpu::CPUContext,
     lcd::LCD,
+    log::{log, Category, Level},
+    serial::{serial_read, serial_write},
     timer::{timer_read, timer_write},
 };
 
-static SERIAL_DATA: RwLock<[u8; 2]> = RwLock::new([0, 0]);
+// Bits that always read as 1, per register from 0xFF00. Unused and write-only bits read as 1, registers that don't
+// exist as 0xFF (Pan Docs, "Memory Map" and the register pages)
+// NOTICE: DMG values, the CGB registers in 0xFF4C - 0xFF7F aren't emulated
+const IO_READ_MASKS: [u8; 0x80] = [
+    0xC0, 0x00, 0x7E, 0xFF, 0x00, 0x00, 0x00, 0xF8, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xE0, // P1 - IF
+    0x80, 0x3F, 0x00, 0xFF, 0xBF, 0xFF, 0x3F, 0x00, 0xFF, 0xBF, 0x7F, 0xFF, 0x9F, 0xFF, 0xBF, 0xFF, // NR10 - NR34
+    0xFF, 0x00, 0x00, 0xBF, 0x00, 0x00, 0x70, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, // NR41 - NR52
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Wave RAM
+    0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, // LCDC - WX
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, // BANK (write-only)
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+];
+
+// Stub for P1: only the select bits (4 - 5) are kept, the button bits read as nothing pressed
+// NOTICE: Not part of save states, games select the buttons again before every read anyway
+static JOYPAD_SELECT: RwLock<u8> = RwLock::new(0x00);
 
 pub fn io_read(cpu: &CPUContext, address: u16) -> u8 {
-    match address {
-        0xFF01 => SERIAL_DATA.read().unwrap()[0],
-        0xFF02 => SERIAL_DATA.read().unwrap()[1],
+    let value = match address {
+        0xFF00 => 0xCF | *JOYPAD_SELECT.read().unwrap(),
+        0xFF01 | 0xFF02 => serial_read(address),
         addr if between(addr, 0xFF04, 0xFF07) => timer_read(address),
         0xFF0F => cpu.get_int_flags(),
+        addr if between(addr, 0xFF10, 0xFF3F) => APU.read().unwrap().read(address),
         addr if between(addr, 0xFF40, 0xFF4B) => LCD.read().unwrap().read(address),
         _ => {
-            println!("UNSUPPORTED: Bus.read({address:04X}): I/O Registers");
-            0
+            log(Category::Bus, Level::Debug, format_args!("Unmapped I/O read: {address:04X}"));
+            0xFF
         }
-    }
+    };
+
+    value | IO_READ_MASKS[(address - 0xFF00) as usize]
 }
 
 pub fn io_write(cpu: &mut CPUContext, address: u16, value: u8) {
     match address {
-        0xFF01 => SERIAL_DATA.write().unwrap()[0] = value,
-        0xFF02 => SERIAL_DATA.write().unwrap()[1] = value,
+        0xFF00 => *JOYPAD_SELECT.write().unwrap() = value & 0x30,
+        0xFF01 | 0xFF02 => serial_write(address, value),
         addr if between(addr, 0xFF04, 0xFF07) => timer_write(address, value),
         0xFF0F => cpu.set_int_flags(value),
+        addr if between(addr, 0xFF10, 0xFF3F) => APU.write().unwrap().write(address, value),
         addr if between(addr, 0xFF40, 0xFF4B) => LCD.write().unwrap().write(address, value),
-        _ => println!("UNSUPPORTED: Bus.write({address:04X}): I/O Registers"),
+        0xFF50 => BOOT.write().unwrap().write(value),
+        _ => log(Category::Bus, Level::Debug, format_args!("Unmapped I/O write: {address:04X} = {value:02X}")),
     }
 }
@@ -1,6 +1,6 @@
-use std::sync::RwLock;
+use std::sync::{atomic::{AtomicBool, Ordering}, RwLock};
 
-use super::{common::{bit, bit_set, COLORS}, dma::DMA};
+use super::{common::{bit, bit_set, COLORS, PALETTE}, dma::DMA};
 
 pub struct LCDContext {
     // Registers,
@@ -41,6 +41,9 @@ pub static LCD: RwLock<LCDContext> = RwLock::new(LCDContext {
     sprite2_colors: [COLORS[0], COLORS[1], COLORS[2], COLORS[3]],
 });
 
+// Gameboy-Doctor's reference logs were made with LY always reading 0x90, so traces only match with this set
+pub static STUB_LY: AtomicBool = AtomicBool::new(false);
+
 impl LCDContext {
     pub fn read(&self, address: u16) -> u8 { // NOTICE: NEEDS COLOSSAL REFACTORING
         match address {
@@ -48,7 +51,7 @@ impl LCDContext {
             0xFF41 => self.status,
             0xFF42 => self.scroll_y,
             0xFF43 => self.scroll_x,
-            0xFF44 => self.line_y,
+            0xFF44 => if STUB_LY.load(Ordering::Relaxed) { 0x90 } else { self.line_y },
             0xFF45 => self.line_y_compare,
             0xFF46 => self.dma,
             0xFF47 => self.bg_palette,
@@ -369,10 +372,18 @@ impl LCDContext {
             _ => unreachable!()
         };
 
-        colors[0] = COLORS[(palette_data & 0b11) as usize];
-        colors[1] = COLORS[((palette_data >> 2) & 0b11) as usize];
-        colors[2] = COLORS[((palette_data >> 4) & 0b11) as usize];
-        colors[3] = COLORS[((palette_data >> 6) & 0b11) as usize];
+        let palette = PALETTE.read().unwrap();
+        colors[0] = palette[(palette_data & 0b11) as usize];
+        colors[1] = palette[((palette_data >> 2) & 0b11) as usize];
+        colors[2] = palette[((palette_data >> 4) & 0b11) as usize];
+        colors[3] = palette[((palette_data >> 6) & 0b11) as usize];
+    }
+
+    // Recalculates the colors after PALETTE has been changed
+    pub fn refresh_palettes(&mut self) {
+        self.update_palette(self.bg_palette, 0);
+        self.update_palette(self.obj1_palette & (!0b11), 1);
+        self.update_palette(self.obj2_palette & (!0b11), 2);
     }
 
     // Control
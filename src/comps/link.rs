@@ -0,0 +1,144 @@
+// Link cable to another gbemu over TCP. The side driving the clock waits for the other one to answer each byte. A
+// side that isn't waiting for a transfer answers right away with SB from its reader thread, one that is answers once
+// its emulation polls for the transfer, which keeps the two emulators in lockstep on real transfers.
+// NOTICE: The other side is always another process, two Game Boys in one gbemu aren't supported since the emulator
+// state lives in statics
+
+use std::{
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{mpsc::{self, Receiver}, Arc, Mutex},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use super::{log::{log, Category, Level}, serial::SerialDevice};
+
+// Messages are [kind, sequence, byte]
+const TRANSFER: u8 = 0; // From the side driving the clock
+const REPLY: u8 = 1;    // The other side's byte
+
+// How long the side driving the clock waits before reading 0xFF, like with nothing plugged in. A transfer older than
+// that isn't answered anymore, the other side already went on without it
+const REPLY_TIMEOUT: Duration = Duration::from_secs(1);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Shared with the reader thread
+struct Shared {
+    stream: TcpStream,
+    data: u8,      // SB
+    waiting: bool, // For the other side to drive the clock
+}
+
+pub struct LinkCable {
+    shared: Arc<Mutex<Shared>>,
+    messages: Mutex<Receiver<([u8; 3], Instant)>>, // When each came in, in a Mutex only so the cable is Sync
+    sequence: u8,
+}
+
+impl LinkCable {
+    pub fn listen(port: u16) -> io::Result<LinkCable> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        let (stream, _) = listener.accept()?;
+
+        LinkCable::new(stream)
+    }
+
+    // Keeps trying for a while, so the two emulators can be started in any order
+    pub fn connect(address: &str) -> io::Result<LinkCable> {
+        let start = Instant::now();
+
+        loop {
+            match TcpStream::connect(address) {
+                Ok(stream) => return LinkCable::new(stream),
+                Err(_) if start.elapsed() < CONNECT_TIMEOUT => sleep(Duration::from_millis(100)),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn new(stream: TcpStream) -> io::Result<LinkCable> {
+        stream.set_nodelay(true)?;
+
+        let mut reader = stream.try_clone()?;
+        let shared = Arc::new(Mutex::new(Shared { stream, data: 0, waiting: false }));
+        let (sender, receiver) = mpsc::channel();
+
+        // Stops when the other side disconnects, from then on the cable acts unplugged
+        let reader_shared = shared.clone();
+        std::thread::spawn(move || {
+            let mut message = [0; 3];
+
+            while reader.read_exact(&mut message).is_ok() {
+                let mut shared = reader_shared.lock().unwrap();
+
+                if message[0] == TRANSFER && !shared.waiting {
+                    // No transfer going on this side, the byte sent is lost
+                    let reply = [REPLY, message[1], shared.data];
+                    send(&mut shared, reply);
+                } else if sender.send((message, Instant::now())).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(LinkCable { shared, messages: Mutex::new(receiver), sequence: 0 })
+    }
+}
+
+fn send(shared: &mut Shared, message: [u8; 3]) {
+    // A closed connection shows up on the reading side
+    let _ = shared.stream.write_all(&message);
+}
+
+// The other side timed out waiting for the answer to this transfer, answering now would hand the byte to a later one
+fn stale(received: Instant) -> bool {
+    if received.elapsed() < REPLY_TIMEOUT {
+        return false;
+    }
+
+    log(Category::Serial, Level::Warn, format_args!("Dropped a link cable transfer that timed out, the two sides may be out of sync"));
+    true
+}
+
+impl SerialDevice for LinkCable {
+    fn transfer(&mut self, byte: u8) -> u8 {
+        self.sequence = self.sequence.wrapping_add(1);
+        send(&mut self.shared.lock().unwrap(), [TRANSFER, self.sequence, byte]);
+
+        let deadline = Instant::now() + REPLY_TIMEOUT;
+        let messages = self.messages.get_mut().unwrap();
+
+        loop {
+            match messages.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(([REPLY, sequence, reply], _)) if sequence == self.sequence => return reply,
+                // Came in while the Game Boy was still waiting for the other side's clock
+                Ok(([TRANSFER, sequence, _], received)) if !stale(received) => {
+                    send(&mut self.shared.lock().unwrap(), [REPLY, sequence, byte]);
+                }
+                Ok(_) => {} // The answer to a transfer that timed out, or a stale one
+                Err(_) => {
+                    log(Category::Serial, Level::Warn, format_args!("No answer over the link cable, reading 0xFF"));
+                    return 0xFF;
+                }
+            }
+        }
+    }
+
+    fn poll_transfer(&mut self, byte: u8) -> Option<u8> {
+        while let Ok(([kind, sequence, other], received)) = self.messages.get_mut().unwrap().try_recv() {
+            if kind == TRANSFER && !stale(received) {
+                send(&mut self.shared.lock().unwrap(), [REPLY, sequence, byte]);
+                return Some(other);
+            }
+        }
+
+        None
+    }
+
+    fn state_changed(&mut self, data: u8, waiting: bool) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.data = data;
+        shared.waiting = waiting;
+    }
+}
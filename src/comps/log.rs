@@ -0,0 +1,175 @@
+// Diagnostic output on stderr, filtered per category and level
+
+use std::{collections::BTreeMap, fmt::{self, Display}, panic::Location, str::FromStr, sync::{atomic::{AtomicU8, Ordering}, Mutex}, time::{Duration, Instant}};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Category {
+    Cart,
+    Bus,
+    PPU,
+    CPU,
+    Serial,
+    Timing,
+    UI,
+}
+
+const CATEGORIES: [Category; 7] = [
+    Category::Cart, Category::Bus, Category::PPU, Category::CPU, Category::Serial, Category::Timing, Category::UI,
+];
+
+pub const CATEGORY_NAMES: &str = "cart, bus, ppu, cpu, serial, timing, ui";
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Level {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+pub const LEVEL_NAMES: &str = "off, error, warn, info, debug";
+
+impl FromStr for Category {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CATEGORIES
+            .into_iter()
+            .find(|category| category.to_string() == s.to_lowercase())
+            .ok_or(format!("Unknown log category: {s} (expected one of {CATEGORY_NAMES})"))
+    }
+}
+
+impl Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let str = match *self {
+            Category::Cart => "cart",
+            Category::Bus => "bus",
+            Category::PPU => "ppu",
+            Category::CPU => "cpu",
+            Category::Serial => "serial",
+            Category::Timing => "timing",
+            Category::UI => "ui",
+        };
+
+        write!(f, "{}", str)
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(Level::Off),
+            "error" => Ok(Level::Error),
+            "warn" => Ok(Level::Warn),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            _ => Err(format!("Unknown log level: {s} (expected one of {LEVEL_NAMES})")),
+        }
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let str = match *self {
+            Level::Off => "OFF",
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+        };
+
+        f.pad(str) // Lines are aligned by padding the level
+    }
+}
+
+// Checked by every call, even the ones that end up filtered, so the levels are kept outside of the lock
+static LEVELS: [AtomicU8; 7] = [const { AtomicU8::new(Level::Info as u8) }; 7];
+
+// Warn and debug messages come from per access or per cycle checks and can flood stderr, so each call site gets
+// RATE_LIMIT of them per RATE_WINDOW. The rest are counted and reported once the window is over.
+// Errors and info (like the serial output of test ROMs) always get through
+const RATE_LIMIT: u32 = 10;
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+struct RateLimit {
+    level: Level,
+    category: Category,
+    window_start: Instant,
+    count: u32,
+    suppressed: u32,
+}
+
+static RATE_LIMITS: Mutex<BTreeMap<&'static Location<'static>, RateLimit>> = Mutex::new(BTreeMap::new());
+
+pub fn log_set_level(category: Category, level: Level) {
+    LEVELS[category as usize].store(level as u8, Ordering::Relaxed);
+}
+
+pub fn log_enabled(category: Category, level: Level) -> bool {
+    level != Level::Off && level as u8 <= LEVELS[category as usize].load(Ordering::Relaxed)
+}
+
+// Parses LEVEL or CATEGORY=LEVEL, separated by commas, e.g. warn,bus=debug. No category means all of them
+pub fn parse_log_spec(spec: &str) -> Result<Vec<(Option<Category>, Level)>, String> {
+    spec.split(',')
+        .map(|part| match part.split_once('=') {
+            Some((category, level)) => Ok((Some(category.parse()?), level.parse()?)),
+            None => Ok((None, part.parse()?)),
+        })
+        .collect()
+}
+
+pub fn log_configure(spec: &[(Option<Category>, Level)]) {
+    for (category, level) in spec {
+        match category {
+            Some(category) => log_set_level(*category, *level),
+            None => CATEGORIES.into_iter().for_each(|category| log_set_level(category, *level)),
+        }
+    }
+}
+
+#[track_caller]
+pub fn log(category: Category, level: Level, message: fmt::Arguments) {
+    if !log_enabled(category, level) {
+        return;
+    }
+
+    if !matches!(level, Level::Warn | Level::Debug) {
+        eprintln!("{level:5} [{category}] {message}");
+        return;
+    }
+
+    let now = Instant::now();
+    let mut limits = RATE_LIMITS.lock().unwrap();
+    let limit = limits.entry(Location::caller()).or_insert(RateLimit { level, category, window_start: now, count: 0, suppressed: 0 });
+
+    if RATE_WINDOW <= now - limit.window_start {
+        if limit.suppressed != 0 {
+            eprintln!("{level:5} [{category}] ({} similar messages suppressed)", limit.suppressed);
+        }
+
+        *limit = RateLimit { level, category, window_start: now, count: 0, suppressed: 0 };
+    }
+
+    if limit.count == RATE_LIMIT {
+        limit.suppressed += 1;
+        return;
+    }
+
+    limit.count += 1;
+    eprintln!("{level:5} [{category}] {message}");
+}
+
+// Reports the messages still counted as suppressed, called before exiting
+pub fn log_flush() {
+    for limit in RATE_LIMITS.lock().unwrap().values_mut() {
+        if limit.suppressed != 0 {
+            eprintln!("{:5} [{}] ({} similar messages suppressed)", limit.level, limit.category, limit.suppressed);
+            limit.suppressed = 0;
+        }
+    }
+}
@@ -13,8 +13,26 @@ pub mod interrupts;
 pub mod cpu_util;
 pub mod cpu_fetch;
 pub mod io;
-pub mod dbg;
 pub mod dma;
 pub mod lcd;
 pub mod ppu_sm;
-pub mod ppu_pipeline;
\ No newline at end of file
+pub mod ppu_pipeline;
+pub mod frame;
+pub mod png;
+pub mod trace;
+pub mod boot;
+pub mod apu;
+pub mod model;
+pub mod state;
+pub mod rewind;
+pub mod pacing;
+pub mod debugger;
+pub mod expr;
+pub mod breakpoints;
+pub mod gdb;
+pub mod disasm;
+pub mod symbols;
+pub mod mooneye;
+pub mod log;
+pub mod serial;
+pub mod link;
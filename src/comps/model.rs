@@ -0,0 +1,138 @@
+// Hardware models and the state their boot ROMs leave behind
+
+use std::{fmt::Display, str::FromStr, sync::RwLock};
+
+use super::{cpu::CPUContext, io::io_write, lcd::LCD, timer::TIMER};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Model {
+    DMG0, // Early DMG boot ROM
+    DMG,
+    MGB,  // Game Boy Pocket
+    SGB,
+    SGB2,
+    CGB,  // NOTICE: Only the post-boot state, the CGB hardware itself isn't emulated
+}
+
+pub static MODEL: RwLock<Model> = RwLock::new(Model::DMG);
+
+pub const MODEL_NAMES: &str = "dmg0, dmg, mgb, sgb, sgb2, cgb";
+
+impl FromStr for Model {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "dmg0" => Ok(Model::DMG0),
+            "dmg" => Ok(Model::DMG),
+            "mgb" => Ok(Model::MGB),
+            "sgb" => Ok(Model::SGB),
+            "sgb2" => Ok(Model::SGB2),
+            "cgb" => Ok(Model::CGB),
+            _ => Err(format!("Unknown model: {s} (expected one of {MODEL_NAMES})")),
+        }
+    }
+}
+
+impl Display for Model {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match *self {
+            Model::DMG0 => "DMG0",
+            Model::DMG => "DMG",
+            Model::MGB => "MGB",
+            Model::SGB => "SGB",
+            Model::SGB2 => "SGB2",
+            Model::CGB => "CGB",
+        };
+
+        write!(f, "{}", str)
+    }
+}
+
+// I/O registers that end up the same on every model (Pan Docs, "Power Up Sequence")
+const POST_BOOT_IO: [(u16, u8); 27] = [
+    (0xFF01, 0x00), // SB
+    (0xFF05, 0x00), // TIMA
+    (0xFF06, 0x00), // TMA
+    (0xFF07, 0xF8), // TAC
+    (0xFF0F, 0xE1), // IF
+    (0xFF10, 0x80), // NR10
+    (0xFF11, 0xBF), // NR11
+    (0xFF12, 0xF3), // NR12
+    (0xFF13, 0xFF), // NR13
+    (0xFF14, 0xBF), // NR14
+    (0xFF16, 0x3F), // NR21
+    (0xFF17, 0x00), // NR22
+    (0xFF18, 0xFF), // NR23
+    (0xFF19, 0xBF), // NR24
+    (0xFF1A, 0x7F), // NR30
+    (0xFF1B, 0xFF), // NR31
+    (0xFF1C, 0x9F), // NR32
+    (0xFF1D, 0xFF), // NR33
+    (0xFF1E, 0xBF), // NR34
+    (0xFF20, 0xFF), // NR41
+    (0xFF21, 0x00), // NR42
+    (0xFF22, 0x00), // NR43
+    (0xFF23, 0xBF), // NR44
+    (0xFF24, 0x77), // NR50
+    (0xFF25, 0xF3), // NR51
+    (0xFF40, 0x91), // LCDC
+    (0xFF47, 0xFC), // BGP
+];
+
+// Sets the registers to what the model's boot ROM leaves them as when it jumps to 0x0100
+pub fn model_post_boot(cpu: &mut CPUContext, model: Model, header_checksum: u8) {
+    type M = Model;
+
+    // The DMG and MGB boot ROMs leave H and C set unless the header checksum is 0
+    let checksum_flags = if header_checksum != 0 { 0x30 } else { 0x00 };
+
+    let (af, bc, de, hl) = match model {
+        M::DMG0 => (0x0100, 0xFF13, 0x00C1, 0x8403),
+        M::DMG => (0x0180 | checksum_flags, 0x0013, 0x00D8, 0x014D),
+        M::MGB => (0xFF80 | checksum_flags, 0x0013, 0x00D8, 0x014D),
+        M::SGB => (0x0100, 0x0014, 0x0000, 0xC060),
+        M::SGB2 => (0xFF00, 0x0014, 0x0000, 0xC060),
+        M::CGB => (0x1180, 0x0000, 0xFF56, 0x000D),
+    };
+
+    cpu.registers.a = (af >> 8) as u8;
+    cpu.registers.f = af as u8;
+    cpu.registers.b = (bc >> 8) as u8;
+    cpu.registers.c = bc as u8;
+    cpu.registers.d = (de >> 8) as u8;
+    cpu.registers.e = de as u8;
+    cpu.registers.h = (hl >> 8) as u8;
+    cpu.registers.l = hl as u8;
+    cpu.registers.sp = 0xFFFE;
+    cpu.registers.pc = 0x100;
+    cpu.int_master_enabled = false;
+    cpu.set_ie_reg(0);
+
+    for (address, value) in POST_BOOT_IO {
+        io_write(cpu, address, value);
+    }
+
+    // Registers that differ between models
+    let (div, sc, nr52, stat, dma) = match model {
+        M::DMG0 => (0x1830, 0x7E, 0xF1, 0x81, 0xFF),
+        M::DMG | M::MGB => (0xABCC, 0x7E, 0xF1, 0x85, 0xFF),
+        M::SGB | M::SGB2 => (0xABCC, 0x7E, 0xF0, 0x85, 0xFF), // NOTICE: DIV isn't documented for the SGB, using the DMG value
+        M::CGB => (0xABCC, 0x7F, 0xF1, 0x85, 0x00),           // NOTICE: DIV isn't documented for the CGB, using the DMG value
+    };
+
+    io_write(cpu, 0xFF02, sc);
+    io_write(cpu, 0xFF26, nr52);
+
+    TIMER.write().unwrap().div = div;
+
+    // Written directly, going through LCD.write() would start a DMA transfer and change the PPU mode
+    let mut lcd = LCD.write().unwrap();
+    lcd.status = (stat & !0b11) | (lcd.status & 0b11);
+    lcd.dma = dma;
+    lcd.obj1_palette = 0xFF;
+    lcd.obj2_palette = 0xFF;
+    lcd.refresh_palettes();
+
+    *MODEL.write().unwrap() = model;
+}
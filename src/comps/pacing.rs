@@ -0,0 +1,72 @@
+// Frame pacing: keeps the emulation at the chosen speed, controlled by the frontend
+
+use std::{sync::RwLock, time::{Duration, Instant}};
+
+use super::{log::{log, Category, Level}, ppu::{LINES_PER_FRAME, TICKS_PER_LINE}};
+
+// 70224 dots at 4.194304 MHz, about 59.73 frames per second
+const FRAME_TIME: Duration = Duration::from_nanos(TICKS_PER_LINE as u64 * LINES_PER_FRAME as u64 * 1_000_000_000 / 4_194_304);
+
+pub const MIN_SPEED: f32 = 0.125;
+pub const MAX_SPEED: f32 = 8.0;
+
+pub struct PacingContext {
+    pub speed: f32,    // Speed multiplier, 0 runs uncapped
+    pub turbo: bool,   // Runs uncapped while set, regardless of speed
+    pub advance: bool, // Runs a single frame while the emulator is paused
+    next_frame: Option<Instant>,
+    fps_start: Option<Instant>,
+    fps_frames: u32,
+}
+
+pub static PACING: RwLock<PacingContext> = RwLock::new(PacingContext {
+    speed: 1.0,
+    turbo: false,
+    advance: false,
+    next_frame: None,
+    fps_start: None,
+    fps_frames: 0,
+});
+
+impl PacingContext {
+    // Called once a frame has been emulated, returns how long to wait before starting the next one
+    pub fn frame_done(&mut self) -> Duration {
+        let now = Instant::now();
+        self.advance = false;
+
+        let fps_start = *self.fps_start.get_or_insert(now);
+        self.fps_frames += 1;
+
+        if Duration::from_secs(1) <= now - fps_start {
+            log(Category::Timing, Level::Info, format_args!("FPS: {}", self.fps_frames));
+            self.fps_start = Some(now);
+            self.fps_frames = 0;
+        }
+
+        if self.turbo || self.speed <= 0.0 {
+            self.next_frame = None;
+            return Duration::ZERO;
+        }
+
+        let frame_time = FRAME_TIME.div_f32(self.speed);
+        let next_frame = self.next_frame.map_or(now, |next| next + frame_time);
+
+        // Running behind (or resuming from a pause): start over instead of rushing to catch up
+        if next_frame + frame_time < now {
+            self.next_frame = Some(now);
+            return Duration::ZERO;
+        }
+
+        self.next_frame = Some(next_frame);
+        next_frame.saturating_duration_since(now)
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = if self.speed <= 0.0 { MAX_SPEED } else { (self.speed * 2.0).min(MAX_SPEED) };
+    }
+
+    // Uncapped goes back to normal speed rather than the fastest capped one
+    pub fn slower(&mut self) {
+        self.speed = if self.speed <= 0.0 { 1.0 } else { (self.speed / 2.0).max(MIN_SPEED) };
+    }
+}
@@ -0,0 +1,167 @@
+// Minimal PNG encoder for screenshots. Image data is stored uncompressed (deflate "stored" blocks)
+// read_png only decodes what write_png produces, which is enough for comparing against reference screenshots
+
+use std::{fs::File, io::{self, BufWriter, Write}};
+
+pub fn write_png(path: &str, width: u32, height: u32, pixels: &[u32]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+
+    file.write_all(&[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'])?;
+
+    // IHDR: 8 bits per channel, color type 6 (RGBA)
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+    write_chunk(&mut file, b"IHDR", &ihdr)?;
+
+    // Every scanline starts with filter type 0 (none)
+    let mut raw = Vec::with_capacity((height * (width * 4 + 1)) as usize);
+
+    for row in pixels.chunks(width as usize) {
+        raw.push(0);
+
+        // Pixels are ABGR8888, so the little endian bytes are R, G, B, A
+        for pixel in row {
+            raw.extend_from_slice(&pixel.to_le_bytes());
+        }
+    }
+
+    write_chunk(&mut file, b"IDAT", &zlib_stored(&raw))?;
+    write_chunk(&mut file, b"IEND", &[])?;
+
+    file.flush()
+}
+
+// Reads back a PNG written by write_png, returning (width, height, pixels)
+pub fn read_png(path: &str) -> io::Result<(u32, u32, Vec<u32>)> {
+    let data = std::fs::read(path)?;
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{path}: {msg}"));
+
+    if data.len() < 8 || data[0..8] != [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'] {
+        return Err(invalid("not a PNG file"));
+    }
+
+    let mut width = 0;
+    let mut height = 0;
+    let mut zlib = vec![];
+    let mut pos = 8;
+
+    while pos + 8 <= data.len() {
+        let len = u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+        let kind = &data[pos + 4..pos + 8];
+        let body = data.get(pos + 8..pos + 8 + len).ok_or_else(|| invalid("truncated chunk"))?;
+
+        match kind {
+            b"IHDR" => {
+                if body.len() != 13 {
+                    return Err(invalid("invalid IHDR chunk"));
+                }
+
+                width = u32::from_be_bytes(body[0..4].try_into().unwrap());
+                height = u32::from_be_bytes(body[4..8].try_into().unwrap());
+
+                if body[8..13] != [8, 6, 0, 0, 0] {
+                    return Err(invalid("only 8-bit RGBA images are supported"));
+                }
+            }
+            b"IDAT" => zlib.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+
+        pos += len + 12;
+    }
+
+    // Undo the stored deflate blocks
+    let mut raw = vec![];
+    let mut pos = 2;
+
+    loop {
+        let header = *zlib.get(pos).ok_or_else(|| invalid("truncated image data"))?;
+
+        if header & 0b110 != 0 {
+            return Err(invalid("only uncompressed image data is supported"));
+        }
+
+        let len = zlib.get(pos + 1..pos + 3).ok_or_else(|| invalid("truncated image data"))?;
+        let len = u16::from_le_bytes(len.try_into().unwrap()) as usize;
+        raw.extend_from_slice(zlib.get(pos + 5..pos + 5 + len).ok_or_else(|| invalid("truncated image data"))?);
+        pos += 5 + len;
+
+        if header & 1 != 0 {
+            break;
+        }
+    }
+
+    let stride = width as usize * 4 + 1;
+
+    if raw.len() != stride * height as usize {
+        return Err(invalid("image data has the wrong size"));
+    }
+
+    let pixels = raw
+        .chunks(stride)
+        .flat_map(|row| row[1..].chunks(4).map(|p| u32::from_le_bytes(p.try_into().unwrap())))
+        .collect();
+
+    Ok((width, height, pixels))
+}
+
+
+fn write_chunk(file: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    file.write_all(&(data.len() as u32).to_be_bytes())?;
+    file.write_all(kind)?;
+    file.write_all(data)?;
+
+    let crc = crc32(&[kind.as_slice(), data].concat());
+    file.write_all(&crc.to_be_bytes())
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xFFFF).peekable();
+
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none() as u8;
+        let len = block.len() as u16;
+
+        out.push(last);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFF;
+
+    for byte in data {
+        crc ^= *byte as u32;
+
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
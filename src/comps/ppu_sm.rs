@@ -1,12 +1,6 @@
-use std::sync::{RwLockWriteGuard, RwLock};
+use std::sync::RwLockWriteGuard;
 
-use super::{ppu::{PPUContext, TICKS_PER_LINE, LINES_PER_FRAME, Y_RES, FetchState, X_RES}, lcd::{LCDMode, LCDContext, StatusSource}, cpu::{CPU, CPUContext}, interrupts::InterruptType, common::{TIME, delay}};
-
-const TARGET_FRAME_TIME: u32 = 1000 / 60; // 60 frames per second
-
-static PREV_FRAME_TIME: RwLock<u32> = RwLock::new(0);
-static START_TIMER: RwLock<u32> = RwLock::new(0);
-static FRAME_COUNT: RwLock<u32> = RwLock::new(0);
+use super::{ppu::{PPUContext, TICKS_PER_LINE, LINES_PER_FRAME, Y_RES, FetchState, X_RES}, lcd::{LCDMode, LCDContext, StatusSource}, cpu::CPUContext, interrupts::InterruptType, frame::FRAMES};
 
 impl PPUContext {
     fn increment_line_y(&mut self, lcd: &mut RwLockWriteGuard<LCDContext>, cpu: &mut CPUContext) {
@@ -61,25 +55,7 @@ impl PPUContext {
                 }
 
                 self.current_frame += 1;
-
-                // Calculate FPS
-                let end = TIME.read().unwrap().unwrap().elapsed().as_millis() as u32;
-                let frame_time = end - *PREV_FRAME_TIME.read().unwrap();
-
-                if frame_time < TARGET_FRAME_TIME {
-                    delay((TARGET_FRAME_TIME - frame_time) as u64);
-                }
-
-                if 1000 <= end - *START_TIMER.read().unwrap() {
-                    let fps = *FRAME_COUNT.read().unwrap();
-                    *START_TIMER.write().unwrap() = end;
-                    *FRAME_COUNT.write().unwrap() = 0;
-
-                    println!("FPS: {fps}");
-                }
-
-                *FRAME_COUNT.write().unwrap() += 1;
-                *PREV_FRAME_TIME.write().unwrap() = TIME.read().unwrap().unwrap().elapsed().as_millis() as u32;
+                FRAMES.publish(self.current_frame, &self.frame_buffer, &self.vram);
             } else {
                 lcd.status_mode_set(LCDMode::OAM);
             }
@@ -1,8 +1,8 @@
 use std::sync::RwLock;
 
 pub struct RAMContext {
-    wram: [u8; 0x2000],
-    hram: [u8; 0x80],
+    pub wram: [u8; 0x2000],
+    pub hram: [u8; 0x80],
 }
 
 pub static RAM: RwLock<RAMContext> = RwLock::new(RAMContext {
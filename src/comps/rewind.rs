@@ -0,0 +1,139 @@
+// Rewind: a ring buffer of save states taken once per frame
+
+use std::collections::VecDeque;
+
+/*
+    Only the newest snapshot is kept whole, every older one is stored as a delta
+    against the snapshot taken after it:
+
+    |Delta 0|Delta 1|...|Delta N-1|Latest|
+
+    Deltas are the XOR of the two snapshots with runs of zeros (unchanged bytes) collapsed,
+    so a frame where little changed costs a few KB instead of a whole save state.
+    Stepping back undoes the newest delta, dropping the oldest one only removes it from the front.
+*/
+pub struct RewindBuffer {
+    deltas: VecDeque<Vec<u8>>,
+    latest: Option<Vec<u8>>,
+    size: usize,       // Bytes held by the deltas and the latest snapshot
+    pub budget: usize, // Maximum size in bytes
+    pub frames: usize, // Maximum number of snapshots
+}
+
+impl RewindBuffer {
+    pub fn new(budget: usize, frames: usize) -> RewindBuffer {
+        RewindBuffer {
+            deltas: VecDeque::new(),
+            latest: None,
+            size: 0,
+            budget,
+            frames,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.deltas.len() + self.latest.is_some() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+
+    pub fn clear(&mut self) {
+        self.deltas.clear();
+        self.latest = None;
+        self.size = 0;
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        if self.frames == 0 || self.budget == 0 {
+            return;
+        }
+
+        if let Some(prev) = self.latest.take() {
+            let delta = encode_delta(&prev, &state);
+            self.size += delta.len();
+            self.size -= prev.len();
+            self.deltas.push_back(delta);
+        }
+
+        self.size += state.len();
+        self.latest = Some(state);
+
+        while self.len() > self.frames || (self.size > self.budget && !self.deltas.is_empty()) {
+            let oldest = self.deltas.pop_front().unwrap();
+            self.size -= oldest.len();
+        }
+    }
+
+    // Steps back a frame. The newest snapshot is the frame on screen, so it's dropped and the one before it is
+    // returned, which stays in the buffer as the newest
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let delta = self.deltas.pop_back()?;
+        let state = self.latest.take().unwrap();
+        let prev = decode_delta(&state, &delta);
+
+        self.size -= state.len() + delta.len();
+        self.size += prev.len();
+        self.latest = Some(prev.clone());
+
+        Some(prev)
+    }
+}
+
+/*
+    Delta format:
+
+    |Length of the old snapshot u32|
+    |Zero run u16|Literal count u16|Literals|...
+*/
+fn encode_delta(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::with_capacity(old.len() / 8);
+    delta.extend_from_slice(&(old.len() as u32).to_le_bytes());
+
+    // The snapshots can differ in length (the pixel FIFO isn't a fixed size), missing bytes count as 0
+    let xor = |i: usize| old.get(i).copied().unwrap_or(0) ^ new.get(i).copied().unwrap_or(0);
+
+    let mut i = 0;
+    while i < old.len() {
+        let zeros_start = i;
+        while i < old.len() && i - zeros_start < u16::MAX as usize && xor(i) == 0 {
+            i += 1;
+        }
+
+        let literals_start = i;
+        while i < old.len() && i - literals_start < u16::MAX as usize && xor(i) != 0 {
+            i += 1;
+        }
+
+        delta.extend_from_slice(&((literals_start - zeros_start) as u16).to_le_bytes());
+        delta.extend_from_slice(&((i - literals_start) as u16).to_le_bytes());
+        delta.extend((literals_start..i).map(xor));
+    }
+
+    delta
+}
+
+fn decode_delta(new: &[u8], delta: &[u8]) -> Vec<u8> {
+    let len = u32::from_le_bytes(delta[0..4].try_into().unwrap()) as usize;
+
+    let mut old = new.to_vec();
+    old.resize(len, 0);
+
+    let mut i = 0;
+    let mut pos = 4;
+    while pos < delta.len() {
+        let zeros = u16::from_le_bytes([delta[pos], delta[pos + 1]]) as usize;
+        let literals = u16::from_le_bytes([delta[pos + 2], delta[pos + 3]]) as usize;
+        pos += 4;
+        i += zeros;
+
+        for byte in &delta[pos..pos + literals] {
+            old[i] ^= byte;
+            i += 1;
+        }
+        pos += literals;
+    }
+
+    old
+}
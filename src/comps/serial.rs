@@ -0,0 +1,167 @@
+// Serial port (SB and SC) and whatever is plugged into the link port
+
+use std::{fs::File, io::Write, sync::{Mutex, RwLock}};
+
+use super::{cpu::CPUContext, interrupts::InterruptType, log::{log, log_enabled, Category, Level}};
+
+// The other end of the link cable. Bytes are exchanged whole, the shifting happens on the Game Boy side
+pub trait SerialDevice: Send + Sync {
+    // The Game Boy drives the clock and sends byte, returns the byte the device sends back at the same time
+    fn transfer(&mut self, byte: u8) -> u8;
+
+    // The device drives the clock. Polled while the Game Boy waits with byte in SB, returns what the device sent
+    // once it has done a transfer
+    fn poll_transfer(&mut self, byte: u8) -> Option<u8>;
+
+    // Called with SB and whether the Game Boy waits for the device's clock whenever either changes, so the device
+    // can answer a transfer the Game Boy isn't waiting for
+    fn state_changed(&mut self, _data: u8, _waiting: bool) {}
+}
+
+const BIT_CYCLES: u16 = 128; // M-cycles per bit, the internal clock runs at 8192 Hz
+
+pub struct SerialContext {
+    pub data: u8,      // SB
+    pub control: u8,   // SC
+    pub bits_left: u8, // Of the current internal clock transfer
+    pub clock: u16,    // M-cycles until the next bit is shifted, or until the device is polled again
+    pub incoming: u8,  // The byte the device sent, shifted into SB a bit at a time
+    pub device: Option<Box<dyn SerialDevice>>, // Nothing plugged in reads as all ones
+}
+
+pub static SERIAL: RwLock<SerialContext> = RwLock::new(SerialContext {
+    data: 0,
+    control: 0,
+    bits_left: 0,
+    clock: 0,
+    incoming: 0,
+    device: None,
+});
+
+static SERIAL_LINE: Mutex<String> = Mutex::new(String::new());
+static SERIAL_OUT: Mutex<Option<File>> = Mutex::new(None);
+
+pub fn serial_set_output(file: File) {
+    *SERIAL_OUT.lock().unwrap() = Some(file);
+}
+
+// Every byte sent goes to --serial-out as is, and to the debug log a line at a time since test ROMs print their
+// results this way. Games send binary data, so it stays out of the log by default
+fn serial_output(byte: u8) {
+    if log_enabled(Category::Serial, Level::Debug) {
+        let mut line = SERIAL_LINE.lock().unwrap();
+        if byte == b'\n' {
+            log(Category::Serial, Level::Debug, format_args!("{line}"));
+            line.clear();
+        } else {
+            line.push(byte as char);
+        }
+    }
+
+    if let Some(file) = SERIAL_OUT.lock().unwrap().as_mut() {
+        file.write_all(&[byte]).unwrap();
+    }
+}
+
+impl SerialContext {
+    fn transferring(&self) -> bool {
+        self.control & 0x80 != 0
+    }
+
+    fn internal_clock(&self) -> bool {
+        self.control & 0x01 != 0
+    }
+
+    // A transfer in progress needs its clock running, and an internally clocked one bits left to shift in
+    pub fn consistent(&self) -> bool {
+        !self.transferring()
+            || ((1..=BIT_CYCLES).contains(&self.clock) && (!self.internal_clock() || (1..=8).contains(&self.bits_left)))
+    }
+
+    fn start(&mut self) {
+        self.clock = BIT_CYCLES;
+
+        if self.internal_clock() {
+            serial_output(self.data);
+            self.incoming = self.device.as_mut().map_or(0xFF, |device| device.transfer(self.data));
+            self.bits_left = 8;
+        }
+    }
+
+    fn complete(&mut self, cpu: &mut CPUContext) {
+        self.control &= !0x80;
+        cpu.request_interrupt(InterruptType::Serial);
+        self.update_device();
+    }
+
+    fn update_device(&mut self) {
+        let (data, waiting) = (self.data, self.transferring() && !self.internal_clock());
+        if let Some(device) = self.device.as_mut() {
+            device.state_changed(data, waiting);
+        }
+    }
+}
+
+// Called once per M-cycle
+pub fn serial_tick(cpu: &mut CPUContext) {
+    let mut serial = SERIAL.write().unwrap();
+    if !serial.transferring() {
+        return;
+    }
+
+    serial.clock -= 1;
+    if serial.clock != 0 {
+        return;
+    }
+
+    serial.clock = BIT_CYCLES;
+
+    if serial.internal_clock() {
+        // NOTICE: Each transfer starts its own clock, on hardware the bits follow DIV
+        serial.data = (serial.data << 1) | (serial.incoming >> 7);
+        serial.incoming <<= 1;
+        serial.bits_left -= 1;
+
+        if serial.bits_left == 0 {
+            serial.complete(cpu);
+        }
+    } else {
+        // Waits for as long as it takes, like a Game Boy with nothing driving the clock
+        let data = serial.data;
+        if let Some(byte) = serial.device.as_mut().and_then(|device| device.poll_transfer(data)) {
+            serial_output(data);
+            serial.data = byte;
+            serial.complete(cpu);
+        }
+    }
+}
+
+pub fn serial_read(address: u16) -> u8 {
+    let serial = SERIAL.read().unwrap();
+
+    match address {
+        0xFF01 => serial.data,
+        0xFF02 => serial.control,
+        _ => panic!("Invalid serial address!")
+    }
+}
+
+pub fn serial_write(address: u16, value: u8) {
+    let mut serial = SERIAL.write().unwrap();
+
+    match address {
+        0xFF01 => {
+            serial.data = value;
+            serial.update_device();
+        },
+        0xFF02 => {
+            serial.control = value;
+            serial.update_device();
+
+            if serial.transferring() {
+                serial.start();
+            }
+        },
+        _ => panic!("Invalid serial address!")
+    }
+}
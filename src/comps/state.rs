@@ -0,0 +1,390 @@
+// Save states: a snapshot of the whole machine in a versioned binary format
+
+use std::collections::VecDeque;
+
+use super::{
+    apu::APU,
+    boot::BOOT,
+    cart::CART,
+    cpu::{CPUContext, Registers},
+    dma::DMA,
+    emu::EMULATOR,
+    instructions::inst_by_opcode,
+    lcd::LCD,
+    model::{Model, MODEL},
+    ppu::{FetchState, OAMEntry, PPUContext},
+    ram::RAM,
+    serial::SERIAL,
+    timer::TIMER,
+};
+
+/*
+    Layout:
+
+    |Magic "GBSS"|Version u16|
+    |ROM title [u8; 16]|Header checksum u8|Global checksum u16|
+    |Payload length u32|Payload checksum u32|
+    |Payload| -> CPU, PPU, LCD, Timer, DMA, RAM, Boot ROM, APU, Serial, Model, Emulator
+*/
+
+const MAGIC: &[u8; 4] = b"GBSS";
+pub const STATE_VERSION: u16 = 2;
+
+pub struct StateWriter {
+    pub data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.data.push(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self.data.get(self.pos..self.pos + len).ok_or("Save state is truncated")?;
+        self.pos += len;
+
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, String> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    pub fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+}
+
+// Identifies the loaded ROM: title, header checksum and global checksum
+fn rom_id() -> [u8; 19] {
+    let cart = CART.read().unwrap();
+    let mut id = [0; 19];
+
+    for (i, c) in cart.header.title.iter().enumerate() {
+        id[i] = *c as u8;
+    }
+
+    id[16] = cart.header.checksum;
+    id[17..19].copy_from_slice(&cart.header.global_checksum.to_le_bytes());
+
+    id
+}
+
+fn fnv1a(data: &[u8]) -> u32 {
+    data.iter().fold(0x811C9DC5, |hash, byte| (hash ^ *byte as u32).wrapping_mul(0x01000193))
+}
+
+// Must be called between CPU steps, with the CPU and PPU locked by the caller
+pub fn save_state(cpu: &CPUContext, ppu: &PPUContext) -> Vec<u8> {
+    let mut w = StateWriter { data: Vec::with_capacity(0x20000) };
+
+    cpu.save(&mut w);
+    ppu.save(&mut w);
+
+    let lcd = LCD.read().unwrap();
+    w.bytes(&[
+        lcd.control, lcd.status, lcd.scroll_y, lcd.scroll_x, lcd.line_y, lcd.line_y_compare,
+        lcd.dma, lcd.bg_palette, lcd.obj1_palette, lcd.obj2_palette, lcd.window_y, lcd.window_x,
+    ]);
+
+    for colors in [lcd.bg_colors, lcd.sprite1_colors, lcd.sprite2_colors] {
+        colors.iter().for_each(|color| w.u32(*color));
+    }
+    drop(lcd);
+
+    let timer = TIMER.read().unwrap();
+    w.u16(timer.div);
+    w.bytes(&[timer.tima, timer.tma, timer.tac]);
+    drop(timer);
+
+    let dma = DMA.read().unwrap();
+    w.bool(dma.active);
+    w.bytes(&[dma.byte, dma.value, dma.start_delay]);
+    drop(dma);
+
+    let ram = RAM.read().unwrap();
+    w.bytes(&ram.wram);
+    w.bytes(&ram.hram);
+    drop(ram);
+
+    // NOTICE: There are no mapper registers or cartridge RAM yet, so nothing of the cartridge is saved.
+    // The ROM itself is identified by the header, never stored
+    w.bool(BOOT.read().unwrap().mapped);
+    w.bytes(&APU.read().unwrap().registers);
+    let serial = SERIAL.read().unwrap();
+    w.bytes(&[serial.data, serial.control, serial.bits_left, serial.incoming]);
+    w.u16(serial.clock);
+    drop(serial);
+    w.u8(*MODEL.read().unwrap() as u8);
+    w.u64(EMULATOR.read().unwrap().ticks);
+
+    let mut state = StateWriter { data: Vec::with_capacity(w.data.len() + 37) };
+    state.bytes(MAGIC);
+    state.u16(STATE_VERSION);
+    state.bytes(&rom_id());
+    state.u32(w.data.len() as u32);
+    state.u32(fnv1a(&w.data));
+    state.bytes(&w.data);
+
+    state.data
+}
+
+// Must be called between CPU steps, with the CPU and PPU locked by the caller
+pub fn load_state(cpu: &mut CPUContext, ppu: &mut PPUContext, state: &[u8]) -> Result<(), String> {
+    let mut r = StateReader { data: state, pos: 0 };
+
+    if r.bytes(4).map_err(|_| "Not a save state")? != MAGIC {
+        return Err(String::from("Not a save state"));
+    }
+
+    let version = r.u16()?;
+    if version != STATE_VERSION {
+        return Err(format!("Unsupported save state version {version} (expected {STATE_VERSION})"));
+    }
+
+    let id = r.array::<19>()?;
+    if id != rom_id() {
+        let title = id[..16].iter().take_while(|c| **c != 0).map(|c| *c as char).collect::<String>();
+        return Err(format!("Save state belongs to a different ROM ({})", title.trim_end()));
+    }
+
+    let len = r.u32()? as usize;
+    let checksum = r.u32()?;
+    let payload = r.bytes(len)?;
+
+    if fnv1a(payload) != checksum {
+        return Err(String::from("Save state is corrupted"));
+    }
+
+    // Some errors only show up part way through the payload, so a bad file can't leave the machine half-loaded
+    let backup = save_state(cpu, ppu);
+    if let Err(e) = load_payload(cpu, ppu, payload) {
+        load_state(cpu, ppu, &backup).unwrap();
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+fn load_payload(cpu: &mut CPUContext, ppu: &mut PPUContext, payload: &[u8]) -> Result<(), String> {
+    let mut r = StateReader { data: payload, pos: 0 };
+
+    cpu.load(&mut r)?;
+    ppu.load(&mut r)?;
+
+    let mut lcd_guard = LCD.write().unwrap();
+    let lcd = &mut *lcd_guard;
+    let [control, status, scroll_y, scroll_x, line_y, line_y_compare, dma, bg_palette, obj1_palette, obj2_palette, window_y, window_x] = r.array::<12>()?;
+    lcd.control = control;
+    lcd.status = status;
+    lcd.scroll_y = scroll_y;
+    lcd.scroll_x = scroll_x;
+    lcd.line_y = line_y;
+    lcd.line_y_compare = line_y_compare;
+    lcd.dma = dma;
+    lcd.bg_palette = bg_palette;
+    lcd.obj1_palette = obj1_palette;
+    lcd.obj2_palette = obj2_palette;
+    lcd.window_y = window_y;
+    lcd.window_x = window_x;
+
+    for colors in [&mut lcd.bg_colors, &mut lcd.sprite1_colors, &mut lcd.sprite2_colors] {
+        for color in colors.iter_mut() {
+            *color = r.u32()?;
+        }
+    }
+    drop(lcd_guard);
+
+    let mut timer = TIMER.write().unwrap();
+    timer.div = r.u16()?;
+    [timer.tima, timer.tma, timer.tac] = r.array()?;
+    drop(timer);
+
+    let mut dma = DMA.write().unwrap();
+    dma.active = r.bool()?;
+    [dma.byte, dma.value, dma.start_delay] = r.array()?;
+    drop(dma);
+
+    let mut ram = RAM.write().unwrap();
+    ram.wram = r.array()?;
+    ram.hram = r.array()?;
+    drop(ram);
+
+    BOOT.write().unwrap().mapped = r.bool()?;
+    APU.write().unwrap().registers = r.array()?;
+    let mut serial = SERIAL.write().unwrap();
+    [serial.data, serial.control, serial.bits_left, serial.incoming] = r.array()?;
+    serial.clock = r.u16()?;
+    if !serial.consistent() {
+        return Err(String::from("Invalid serial transfer in save state"));
+    }
+    drop(serial);
+
+    *MODEL.write().unwrap() = match r.u8()? {
+        0 => Model::DMG0,
+        1 => Model::DMG,
+        2 => Model::MGB,
+        3 => Model::SGB,
+        4 => Model::SGB2,
+        5 => Model::CGB,
+        model => return Err(format!("Unknown model {model} in save state")),
+    };
+
+    EMULATOR.write().unwrap().ticks = r.u64()?;
+
+    if r.pos != payload.len() {
+        return Err(format!("Save state has {} unexpected bytes at the end", payload.len() - r.pos));
+    }
+
+    Ok(())
+}
+
+impl CPUContext {
+    fn save(&self, w: &mut StateWriter) {
+        let regs = &self.registers;
+        w.bytes(&[regs.a, regs.f, regs.b, regs.c, regs.d, regs.e, regs.h, regs.l]);
+        w.u16(regs.pc);
+        w.u16(regs.sp);
+
+        w.u16(self.fetched_data);
+        w.u16(self.mem_dest);
+        w.bool(self.dest_is_mem);
+        w.u8(self.cur_opcode);
+        w.bool(self.halted);
+        w.bool(self.stepping);
+        w.bool(self.int_master_enabled);
+        w.bool(self.enabling_ime);
+        w.u8(self.int_flags);
+        w.u8(self.ie_register);
+    }
+
+    fn load(&mut self, r: &mut StateReader) -> Result<(), String> {
+        let [a, f, b, c, d, e, h, l] = r.array()?;
+        self.registers = Registers { a, f, b, c, d, e, h, l, pc: r.u16()?, sp: r.u16()? };
+
+        self.fetched_data = r.u16()?;
+        self.mem_dest = r.u16()?;
+        self.dest_is_mem = r.bool()?;
+        self.cur_opcode = r.u8()?;
+        self.cur_inst = inst_by_opcode(self.cur_opcode);
+        self.halted = r.bool()?;
+        r.bool()?; // Stepping belongs to the debugger session, not the machine
+        self.int_master_enabled = r.bool()?;
+        self.enabling_ime = r.bool()?;
+        self.int_flags = r.u8()?;
+        self.ie_register = r.u8()?;
+
+        Ok(())
+    }
+}
+
+impl PPUContext {
+    fn save(&self, w: &mut StateWriter) {
+        for entry in &self.oam_ram {
+            w.bytes(&[entry.y, entry.x, entry.tile, entry.flag]);
+        }
+
+        w.bytes(&self.vram);
+
+        // Pixel FIFO, so states taken in the middle of a line resume correctly
+        let pfc = &self.pfc;
+        w.u8(match pfc.cur_fetch_state {
+            FetchState::TILE => 0,
+            FetchState::DATA0 => 1,
+            FetchState::DATA1 => 2,
+            FetchState::SLEEP => 3,
+            FetchState::PUSH => 4,
+        });
+        w.u8(pfc.pixel_fifo.len() as u8);
+        pfc.pixel_fifo.iter().for_each(|pixel| w.u32(*pixel));
+        w.bytes(&[pfc.line_x, pfc.pushed_x, pfc.fetch_x]);
+        w.bytes(&pfc.bgw_fetch_data);
+        w.bytes(&pfc.fetch_entry_data);
+        w.bytes(&[pfc.map_y, pfc.map_x, pfc.tile_y, pfc.fifo_x]);
+
+        w.u32(self.current_frame);
+        w.u32(self.line_ticks);
+        self.frame_buffer.iter().for_each(|pixel| w.u32(*pixel));
+    }
+
+    fn load(&mut self, r: &mut StateReader) -> Result<(), String> {
+        for entry in self.oam_ram.iter_mut() {
+            let [y, x, tile, flag] = r.array()?;
+            *entry = OAMEntry { y, x, tile, flag };
+        }
+
+        self.vram = r.array()?;
+
+        let pfc = &mut self.pfc;
+        pfc.cur_fetch_state = match r.u8()? {
+            0 => FetchState::TILE,
+            1 => FetchState::DATA0,
+            2 => FetchState::DATA1,
+            3 => FetchState::SLEEP,
+            4 => FetchState::PUSH,
+            state => return Err(format!("Unknown pixel fetcher state {state} in save state")),
+        };
+
+        let fifo_len = r.u8()?;
+        pfc.pixel_fifo = VecDeque::with_capacity(fifo_len as usize);
+        for _ in 0..fifo_len {
+            pfc.pixel_fifo.push_back(r.u32()?);
+        }
+
+        [pfc.line_x, pfc.pushed_x, pfc.fetch_x] = r.array()?;
+        pfc.bgw_fetch_data = r.array()?;
+        pfc.fetch_entry_data = r.array()?;
+        [pfc.map_y, pfc.map_x, pfc.tile_y, pfc.fifo_x] = r.array()?;
+
+        self.current_frame = r.u32()?;
+        self.line_ticks = r.u32()?;
+        for pixel in self.frame_buffer.iter_mut() {
+            *pixel = r.u32()?;
+        }
+
+        Ok(())
+    }
+}
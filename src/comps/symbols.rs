@@ -0,0 +1,115 @@
+// Symbols from RGBDS .sym files, for showing labels in the debugger and traces
+
+use std::{fs, sync::RwLock};
+
+use super::{cart::CART, common::parse_hex};
+
+pub struct Symbol {
+    pub bank: u16,
+    pub address: u16,
+    pub name: String,
+}
+
+pub struct SymbolsContext {
+    pub list: Vec<Symbol>, // Sorted by bank, then address
+}
+
+pub static SYMBOL_TABLE: RwLock<SymbolsContext> = RwLock::new(SymbolsContext {
+    list: Vec::new(),
+});
+
+// A label only covers addresses in the same memory region, so the end of ROM isn't shown as an offset from a WRAM label
+fn region(address: u16) -> u16 {
+    match address {
+        0x0000..=0x3FFF => 0,
+        0x4000..=0x7FFF => 1,
+        0x8000..=0x9FFF => 2,
+        0xA000..=0xBFFF => 3,
+        0xC000..=0xDFFF => 4,
+        0xE000..=0xFDFF => 5,
+        0xFE00..=0xFEFF => 6,
+        0xFF00..=0xFF7F => 7,
+        _ => 8,
+    }
+}
+
+impl SymbolsContext {
+    /*
+        Lines look like `01:4A2F Main.loop`, with the bank and address in hexadecimal.
+        Everything after ; is a comment, RGBDS starts the file with one.
+    */
+    pub fn load(&mut self, path: &str) -> Result<usize, String> {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut list = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let symbol = line
+                .split_once(char::is_whitespace)
+                .and_then(|(location, name)| {
+                    let (bank, address) = location.split_once(':')?;
+                    Some(Symbol {
+                        bank: u16::from_str_radix(bank, 16).ok()?,
+                        address: u16::from_str_radix(address, 16).ok()?,
+                        name: name.trim().to_string(),
+                    })
+                })
+                .ok_or(format!("{path}:{}: expected BANK:ADDR NAME", i + 1))?;
+
+            list.push(symbol);
+        }
+
+        list.sort_by_key(|symbol| (symbol.bank, symbol.address));
+        self.list = list;
+
+        Ok(self.list.len())
+    }
+
+    // Finds the closest label at or before address, as bank:label+offset
+    pub fn lookup(&self, bank: u16, address: u16) -> Option<String> {
+        let index = self.list.partition_point(|symbol| (symbol.bank, symbol.address) <= (bank, address));
+        let symbol = self.list[..index].last()?;
+
+        if symbol.bank != bank || region(symbol.address) != region(address) {
+            return None;
+        }
+
+        match address - symbol.address {
+            0 => Some(format!("{bank:02X}:{}", symbol.name)),
+            offset => Some(format!("{bank:02X}:{}+${offset:X}", symbol.name)),
+        }
+    }
+
+    // Looks a label up by name, returns its bank and address
+    pub fn resolve(&self, name: &str) -> Option<(u16, u16)> {
+        self.list.iter().find(|symbol| symbol.name == name).map(|symbol| (symbol.bank, symbol.address))
+    }
+}
+
+// Bank:label+offset of an address in the current memory map, None without a matching label
+pub fn symbol_at(address: u16) -> Option<String> {
+    let symbols = SYMBOL_TABLE.read().unwrap();
+    if symbols.list.is_empty() {
+        return None;
+    }
+
+    // NOTICE: Only ROM is banked, everything else is bank 0
+    let bank = CART.read().unwrap().rom_bank(address).unwrap_or(0);
+    symbols.lookup(bank, address)
+}
+
+// The name of a label, or a hexadecimal address optionally prefixed with $ or 0x. Labels are looked up first, as
+// names like Dead or Cafe are valid hexadecimal too
+pub fn parse_address(arg: &str) -> Result<u16, String> {
+    SYMBOL_TABLE
+        .read()
+        .unwrap()
+        .resolve(arg)
+        .map(|(_, address)| address)
+        .or_else(|| parse_hex(arg))
+        .ok_or(format!("Invalid address or unknown label: {arg}"))
+}
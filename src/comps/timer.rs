@@ -23,15 +23,13 @@ pub fn timer_tick(cpu: &mut CPUContext) {
 
     timer.div = timer.div.wrapping_add(1);
 
-    let timer_update;
-
-    match timer.tac & 0b11 {
-        0b00 => timer_update = (prev_div & (1 << 9) != 0) && (timer.div & (1 << 9) == 0),
-        0b01 => timer_update = (prev_div & (1 << 3) != 0) && (timer.div & (1 << 3) == 0),
-        0b10 => timer_update = (prev_div & (1 << 5) != 0) && (timer.div & (1 << 5) == 0),
-        0b11 => timer_update = (prev_div & (1 << 7) != 0) && (timer.div & (1 << 7) == 0),
+    let timer_update = match timer.tac & 0b11 {
+        0b00 => (prev_div & (1 << 9) != 0) && (timer.div & (1 << 9) == 0),
+        0b01 => (prev_div & (1 << 3) != 0) && (timer.div & (1 << 3) == 0),
+        0b10 => (prev_div & (1 << 5) != 0) && (timer.div & (1 << 5) == 0),
+        0b11 => (prev_div & (1 << 7) != 0) && (timer.div & (1 << 7) == 0),
         _ => unreachable!()
-    }
+    };
 
     if timer_update && timer.tac & (1 << 2) != 0 { // NOTICE: Rewritten to fit Pan Docs
         match timer.tima.checked_add(1) {
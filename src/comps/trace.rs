@@ -0,0 +1,98 @@
+// Instruction trace, one line per executed instruction
+
+use std::{fs::File, io::{self, BufWriter, Write}, str::FromStr, sync::{atomic::{AtomicU8, Ordering}, Mutex}};
+
+use super::{bus::Bus, cpu::CPUContext, emu::EMULATOR, breakpoints::without_watchpoints, symbols::symbol_at};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    Full,   // Cycle count, disassembly and registers
+    Doctor, // Gameboy-Doctor's format, for diffing against its reference logs
+}
+
+impl FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "full" => Ok(TraceFormat::Full),
+            "doctor" => Ok(TraceFormat::Doctor),
+            _ => Err(format!("Unknown trace format: {s} (expected full or doctor)")),
+        }
+    }
+}
+
+// Checked before every instruction, so the format is kept outside of the lock. 0 is off, otherwise TraceFormat + 1
+static TRACING: AtomicU8 = AtomicU8::new(0);
+static TRACE: Mutex<Option<BufWriter<File>>> = Mutex::new(None);
+
+const BUFFER_SIZE: usize = 1 << 20;
+
+pub fn trace_open(path: &str, format: TraceFormat) -> io::Result<()> {
+    let file = File::create(path)?;
+    trace_close();
+
+    *TRACE.lock().unwrap() = Some(BufWriter::with_capacity(BUFFER_SIZE, file));
+    TRACING.store(format as u8 + 1, Ordering::Relaxed);
+
+    Ok(())
+}
+
+pub fn trace_close() {
+    TRACING.store(0, Ordering::Relaxed);
+
+    if let Some(mut trace) = TRACE.lock().unwrap().take() {
+        trace.flush().unwrap();
+    }
+}
+
+pub fn tracing() -> Option<TraceFormat> {
+    match TRACING.load(Ordering::Relaxed) {
+        0 => None,
+        1 => Some(TraceFormat::Full),
+        _ => Some(TraceFormat::Doctor),
+    }
+}
+
+// Called before the instruction at PC is fetched, e.g.
+// A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02
+pub fn trace_doctor(cpu: &CPUContext, bus: &mut dyn Bus) {
+    let regs = &cpu.registers;
+    let pcmem = without_watchpoints(|| (0..4).map(|i| bus.read(cpu, regs.pc.wrapping_add(i))).collect::<Vec<u8>>());
+
+    if let Some(trace) = TRACE.lock().unwrap().as_mut() {
+        writeln!(trace, "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+            regs.a, regs.f, regs.b, regs.c, regs.d, regs.e, regs.h, regs.l, regs.sp, regs.pc,
+            pcmem[0], pcmem[1], pcmem[2], pcmem[3],
+        ).unwrap();
+    }
+}
+
+// Called after the instruction at pc has been fetched, before it is executed
+pub fn trace_instruction(cpu: &CPUContext, bus: &mut dyn Bus, pc: u16) {
+    let line = without_watchpoints(|| format!("{:08X} - ${:04X}: {:14} ({:02X} {:02X} {:02X}) A: {:02X} F: {:04b} BC: {:02X}{:02X} DE: {:02X}{:02X} HL: {:02X}{:02X}",
+        EMULATOR.read().unwrap().ticks,
+        pc,
+        cpu.inst_string(bus),
+        cpu.cur_opcode,
+        bus.read(cpu, pc.wrapping_add(1)),
+        bus.read(cpu, pc.wrapping_add(2)),
+        cpu.registers.a,
+        cpu.registers.f >> 4,
+        cpu.registers.b,
+        cpu.registers.c,
+        cpu.registers.d,
+        cpu.registers.e,
+        cpu.registers.h,
+        cpu.registers.l,
+    ));
+
+    let symbol = match symbol_at(pc) {
+        Some(symbol) => format!(" ; {symbol}"),
+        None => String::new(),
+    };
+
+    if let Some(trace) = TRACE.lock().unwrap().as_mut() {
+        writeln!(trace, "{line}{symbol}").unwrap();
+    }
+}
@@ -1,182 +1,343 @@
-use std::time::{Duration, Instant};
-
-use gbemu::comps::{bus::bus_read, cart::CART, cpu::CPU, emu::EMULATOR, ppu::{PPU, X_RES, Y_RES}, timer::TIMER, common::{COLORS, TIME}};
-use sdl2::{
-    event::Event, keyboard::Keycode, pixels::{Color, PixelFormatEnum}, rect::Rect, render::Canvas, video::Window,
-    EventPump,
+mod cli;
+#[cfg(feature = "sdl")]
+mod ui;
+
+use std::{fs::{self, File}, path::Path, io::BufRead, net::TcpListener, sync::{atomic::Ordering, mpsc::{self, Receiver, Sender}}, time::Duration};
+
+use cli::{Command, Options};
+use gbemu::comps::{
+    bus::MemoryMap, cart::CART, cpu::CPU, emu::EMULATOR, ppu::{PPU, X_RES, Y_RES}, common::PALETTE,
+    lcd::{LCD, STUB_LY}, log::{log, log_configure, log_flush, Category, Level}, pacing::PACING, trace::{trace_open, trace_close}, serial::{SERIAL, serial_set_output}, link::LinkCable, png::write_png,
+    boot::{BOOT, boot_power_on}, model::{MODEL, model_post_boot}, state::{save_state, load_state},
+    rewind::RewindBuffer, frame::FRAMES, debugger::{DEBUGGER, DEBUGGER_HELP, debugger_before_step, debugger_after_step, debugger_command, debugger_enabled},
+    gdb::gdb_listen, symbols::SYMBOL_TABLE, mooneye::{MOONEYE, mooneye_done},
 };
 
-pub const SCALE: u16 = 2;
+// Sent from the UI and debugger threads, handled by the emulation thread between CPU steps
+pub enum Request {
+    SaveState(u8),
+    LoadState(u8),
+    Rewind, // Step back one frame
+    Debug(String),
+}
 
 fn main() {
-    // Initialize cartridge
     let args: Vec<String> = std::env::args().collect();
-    CART.write().unwrap().load(args);
+
+    let options = match cli::parse(&args) {
+        Ok(Command::Run(options)) => options,
+        Ok(Command::Help) => {
+            print!("{}", cli::USAGE);
+            return;
+        }
+        Err(msg) => {
+            eprintln!("{msg}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(msg) = init(&options) {
+        eprintln!("{msg}");
+        std::process::exit(1);
+    }
+
+    let (requests, receiver) = mpsc::channel();
+
+    if options.debug {
+        start_debugger(requests.clone());
+    }
+
+    if options.headless {
+        emulate(&options, &receiver);
+    } else {
+        run_windowed(&options, requests, receiver);
+    }
+
+    trace_close();
+    log_flush();
+
+    if options.mooneye {
+        match &MOONEYE.read().unwrap().result {
+            Some(Ok(())) => println!("Mooneye test passed"),
+            Some(Err(registers)) => {
+                println!("Mooneye test failed: {registers}");
+                std::process::exit(1);
+            }
+            None => {
+                println!("Mooneye test didn't finish");
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+fn init(options: &Options) -> Result<(), String> {
+    log_configure(&options.log);
+
+    // Initialize cartridge
+    CART.write().unwrap().load(&options.rom).map_err(|e| format!("Couldn't load {}: {e}", options.rom))?;
+
+    // RGBDS writes the symbols next to the ROM
+    let sym = Path::new(&options.rom).with_extension("sym");
+    if sym.exists() {
+        let path = sym.to_string_lossy();
+        let count = SYMBOL_TABLE.write().unwrap().load(&path).map_err(|e| format!("Couldn't load symbols from {path}: {e}"))?;
+        log(Category::Cart, Level::Info, format_args!("Loaded {count} symbols from {path}"));
+    }
+
+    match &options.boot_rom {
+        Some(path) => {
+            BOOT.write().unwrap().load(path).map_err(|e| format!("Couldn't load boot ROM {path}: {e}"))?;
+            boot_power_on(&mut CPU.write().unwrap());
+            *MODEL.write().unwrap() = options.model;
+        }
+        None => {
+            // Skip straight to the post-boot state
+            let checksum = CART.read().unwrap().header.checksum;
+            model_post_boot(&mut CPU.write().unwrap(), options.model, checksum);
+        }
+    }
+
+    if let Some(palette) = options.palette {
+        *PALETTE.write().unwrap() = palette;
+        LCD.write().unwrap().refresh_palettes();
+    }
+
+    PACING.write().unwrap().speed = options.speed;
+    STUB_LY.store(options.stub_ly, Ordering::Relaxed);
+
+    if let Some(path) = &options.trace {
+        trace_open(path, options.trace_format).map_err(|e| format!("Couldn't create {path}: {e}"))?;
+    }
+
+    if let Some(path) = &options.serial_out {
+        let file = File::create(path).map_err(|e| format!("Couldn't create {path}: {e}"))?;
+        serial_set_output(file);
+    }
 
     // Initialize PPU
     // PPU.write().unwrap().init();
 
-    // Initialize SDL and time
-    let sdl_context = sdl2::init().unwrap();
-    *TIME.write().unwrap() = Some(Instant::now());
-    let video_subsystem = sdl_context.video().unwrap();
-
-    let debug_window = video_subsystem
-        .window(
-            "Debug",
-            16 * 8 * SCALE as u32 + 16 * SCALE as u32,
-            32 * 8 * SCALE as u32 + 64 * SCALE as u32,
-        )
-        .position_centered()
-        .build()
-        .unwrap();
+    if options.debug || options.gdb.is_some() {
+        DEBUGGER.write().unwrap().enabled = true;
+        CPU.write().unwrap().stepping = true;
+    }
 
-    let mut debug_canvas = debug_window.into_canvas().build().unwrap();
+    MOONEYE.write().unwrap().enabled = options.mooneye || options.until_ld_b_b;
 
-    let ui_window = video_subsystem.window("Game", X_RES as u32 * SCALE as u32, Y_RES as u32 * SCALE as u32)
-    .position_centered()
-    .build()
-    .unwrap();
+    // Both sides are connected before either starts running
+    if let Some(port) = options.link_listen {
+        println!("Waiting for a link cable on 127.0.0.1:{port}");
+        let cable = LinkCable::listen(port).map_err(|e| format!("Couldn't listen on port {port}: {e}"))?;
+        SERIAL.write().unwrap().device = Some(Box::new(cable));
+    }
 
-    let mut ui_canvas = ui_window.into_canvas().build().unwrap();
+    if let Some(address) = &options.link_connect {
+        let cable = LinkCable::connect(address).map_err(|e| format!("Couldn't connect to {address}: {e}"))?;
+        SERIAL.write().unwrap().device = Some(Box::new(cable));
+    }
 
-    let mut event_pump = sdl_context.event_pump().unwrap();
+    if let Some(port) = options.gdb {
+        let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|e| format!("Couldn't listen on port {port}: {e}"))?;
+        println!("Waiting for a GDB client on 127.0.0.1:{port}");
+        std::thread::spawn(move || gdb_listen(listener));
+    }
 
-    // Initialize CPU on separate thread
-    std::thread::spawn(|| {
-        TIMER.write().unwrap().div = 0xABCC;
+    Ok(())
+}
 
-        while EMULATOR.read().unwrap().running {
-            if EMULATOR.read().unwrap().paused {
-                delay(10);
-                continue;
+// Runs the CPU until the emulator is stopped or the requested number of frames has been emulated
+fn emulate(options: &Options, requests: &Receiver<Request>) {
+    let mut screenshot = options.screenshot.clone();
+    // Nothing can rewind without a window, so don't pay for the snapshots
+    let rewind_frames = if options.headless { 0 } else { options.rewind_frames };
+    let mut rewind = RewindBuffer::new(options.rewind_budget * 1024 * 1024, rewind_frames);
+    let mut last_frame = 0;
+
+    while EMULATOR.read().unwrap().running {
+        let mut cpu = CPU.write().unwrap();
+        let mut ppu = PPU.write().unwrap();
+
+        while let Ok(request) = requests.try_recv() {
+            match request {
+                Request::SaveState(slot) => {
+                    let path = state_path(&options.rom, slot);
+                    match fs::write(&path, save_state(&cpu, &ppu)) {
+                        Ok(()) => log(Category::UI, Level::Info, format_args!("Saved state to slot {slot}")),
+                        Err(e) => log(Category::UI, Level::Error, format_args!("Couldn't save state to {}: {e}", path.display())),
+                    }
+                }
+                Request::LoadState(slot) => {
+                    let path = state_path(&options.rom, slot);
+                    let result = fs::read(&path)
+                        .map_err(|e| e.to_string())
+                        .and_then(|state| load_state(&mut cpu, &mut ppu, &state));
+
+                    match result {
+                        Ok(()) => {
+                            log(Category::UI, Level::Info, format_args!("Loaded state from slot {slot}"));
+                            rewind.clear();
+                            last_frame = ppu.current_frame;
+                            FRAMES.publish(ppu.current_frame, &ppu.frame_buffer, &ppu.vram);
+                        }
+                        Err(e) => log(Category::UI, Level::Error, format_args!("Couldn't load state from {}: {e}", path.display())),
+                    }
+                }
+                Request::Rewind => {
+                    if let Some(state) = rewind.pop() {
+                        load_state(&mut cpu, &mut ppu, &state).unwrap();
+                        last_frame = ppu.current_frame;
+                        FRAMES.publish(ppu.current_frame, &ppu.frame_buffer, &ppu.vram);
+                    }
+                }
+                Request::Debug(line) => match debugger_command(&mut cpu, &mut ppu, &line) {
+                    Ok(output) if !output.is_empty() => println!("{output}"),
+                    Ok(_) => {}
+                    Err(e) => eprintln!("{e}"),
+                },
             }
+        }
 
-            CPU.write().unwrap().step(&mut PPU.write().unwrap()); // LOCKING CPU AND PPU
-            // NOTICE: This means that neither the CPU or PPU are accessible during the step()
+        if debugger_enabled() && !cpu.stepping && debugger_before_step(&cpu, &ppu) {
+            cpu.stepping = true;
         }
-    });
 
-    let mut prev_frame = 0;
-
-    // While the emulator is running
-    while !EMULATOR.read().unwrap().die {
-        delay(100);
-        handle_events(&mut event_pump);
-        
-        if prev_frame != PPU.read().unwrap().current_frame {
-            // Update UI
-            update_ui_window(&mut ui_canvas);
-            update_debug_window(&mut debug_canvas);
+        // Requests are still handled while paused, so states can be loaded and rewound.
+        // Nothing runs while rewinding either, otherwise new frames would be pushed faster than key repeat pops them
+        let emu = EMULATOR.read().unwrap();
+        let held = emu.rewinding || (emu.paused && !PACING.read().unwrap().advance);
+        drop(emu);
+
+        if cpu.stepping || held {
+            drop((cpu, ppu));
+            delay(10);
+            continue;
         }
 
-        prev_frame = PPU.read().unwrap().current_frame;
-    }
-}
+        cpu.step(&mut MemoryMap { ppu: &mut ppu }); // LOCKING CPU AND PPU
+        // NOTICE: This means that neither the CPU or PPU are accessible during the step()
 
-pub fn handle_events(event_pump: &mut EventPump) {
-    for event in event_pump.poll_iter() {
-        match event {
-            Event::Quit { .. } => EMULATOR.write().unwrap().die = true,
-            Event::KeyDown {
-                keycode: Some(Keycode::Escape),
-                ..
-            } => EMULATOR.write().unwrap().die = true,
-            _ => {}
+        if debugger_enabled() && debugger_after_step(&cpu, &ppu) {
+            cpu.stepping = true;
         }
-    }
-}
 
-pub fn display_tile(
-    debug_canvas: &mut Canvas<Window>,
-    start_location: u16,
-    tile_num: u16,
-    x: u16,
-    y: u16,
-) {
-    let mut rect: Rect;
-
-    for line in (0..16).step_by(2) {
-        let cpu = CPU.read().unwrap();
-        let ppu = PPU.read().unwrap();
-        let byte1 = bus_read(&cpu, &ppu, start_location + (tile_num * 16) + line);
-        let byte2 = bus_read(&cpu, &ppu, start_location + (tile_num * 16) + line + 1);
-        drop(cpu);
-        drop(ppu);
-
-        for bit in (0..8).rev() {
-            let hi_bit = ((byte1 >> bit) & 1) << 1;
-            let lo_bit = (byte2 >> bit) & 1;
-
-            let color = hi_bit | lo_bit;
-            
-            // NOTICE: This seems kinda fucked
-            debug_canvas.set_draw_color(Color::from_u32(&PixelFormatEnum::ABGR8888.try_into().unwrap(), COLORS[color as usize]));
-
-            rect = Rect::new(
-                (x as i32 + (7 - bit) as i32) * SCALE as i32,
-                (y as i32 + (line as i32 / 2)) * SCALE as i32,
-                SCALE.into(),
-                SCALE.into(),
-            );
-
-            debug_canvas.draw_rect(rect).unwrap();
+        let frame_done = ppu.current_frame != last_frame;
+        if frame_done {
+            last_frame = ppu.current_frame;
+            // Snapshot right after each frame, so the frame buffer holds the finished frame
+            rewind.push(save_state(&cpu, &ppu));
         }
-    }
-}
 
-pub fn update_ui_window(ui_canvas: &mut Canvas<Window>) {
-    let mut rect = Rect::new(0, 0, SCALE as u32, SCALE as u32);
+        if let Some((frame, path)) = &screenshot {
+            if *frame <= ppu.current_frame {
+                if let Err(e) = write_png(path, X_RES as u32, Y_RES as u32, &ppu.frame_buffer) {
+                    eprintln!("Couldn't save screenshot to {path}: {e}");
+                }
 
-    let frame_buffer = &PPU.read().unwrap().frame_buffer;
+                screenshot = None;
+            }
+        }
+
+        // LD B,B can come in the middle of a frame, so finish it to have a whole one for --screenshot
+        let ld_b_b_done = (options.mooneye || options.until_ld_b_b) && frame_done && mooneye_done();
+
+        if options.frames.is_some_and(|frames| frames <= ppu.current_frame) || ld_b_b_done {
+            if let Some(path) = &options.exit_screenshot {
+                if let Err(e) = write_png(path, X_RES as u32, Y_RES as u32, &ppu.frame_buffer) {
+                    eprintln!("Couldn't save screenshot to {path}: {e}");
+                }
+            }
 
-    for line in 0..Y_RES {
-        for x in 0..X_RES {
-            rect.x = x as i32 * SCALE as i32;
-            rect.y = line as i32 * SCALE as i32;
+            let mut emu = EMULATOR.write().unwrap();
+            emu.running = false;
+            emu.die = true;
+        }
 
-            let color_value = frame_buffer[x as usize + line as usize * X_RES as usize];
-            let color = Color::from_u32(&PixelFormatEnum::ABGR8888.try_into().unwrap() ,color_value);
-            ui_canvas.set_draw_color(color);
-            ui_canvas.fill_rect(rect).unwrap();
+        // Wait for the next frame without holding on to the CPU and PPU
+        drop((cpu, ppu));
+        if frame_done {
+            let wait = PACING.write().unwrap().frame_done();
+            std::thread::sleep(wait);
         }
     }
+}
+
+// Reads debugger commands from stdin, the emulation thread runs them and prints the results
+fn start_debugger(requests: Sender<Request>) {
+    println!("Debugger enabled, stopped at the first instruction\n\n{DEBUGGER_HELP}");
+
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            let quit = matches!(line.trim(), "q" | "quit");
+
+            if requests.send(Request::Debug(line)).is_err() || quit {
+                break;
+            }
+        }
+    });
+}
 
-    ui_canvas.present();
+#[cfg(not(feature = "sdl"))]
+fn run_windowed(_options: &Options, _requests: Sender<Request>, _receiver: Receiver<Request>) {
+    eprintln!("gbemu was built without the sdl feature, only --headless is available");
+    std::process::exit(1);
 }
 
-pub fn update_debug_window(debug_canvas: &mut Canvas<Window>) {
-    let mut x_draw = 0;
-    let mut y_draw = 0;
-    let mut tile_num = 0;
-
-    let (width, height) = debug_canvas.output_size().unwrap();
-
-    debug_canvas.set_draw_color(Color::RGB(11, 11, 11));
-    debug_canvas
-        .fill_rect(Rect::new(0, 0, width, height))
-        .unwrap();
-
-    let address = 0x8000;
-
-    // 384 tiles -> 1 tile is 24 x 16
-    for y in 0..24 {
-        for x in 0..16 {
-            display_tile(
-                debug_canvas,
-                address,
-                tile_num,
-                x_draw + x * SCALE,
-                y_draw + y * SCALE,
-            );
-            x_draw += 4 * SCALE;
-            tile_num += 1;
+#[cfg(feature = "sdl")]
+fn run_windowed(options: &Options, requests: Sender<Request>, receiver: Receiver<Request>) {
+    use ui::{ScaleMode, DEBUG_HEIGHT, DEBUG_WIDTH};
+
+    // Initialize SDL
+    let sdl_context = sdl2::init().unwrap();
+    let video_subsystem = sdl_context.video().unwrap();
+
+    let mut debug_window = options.debug_window.then(|| {
+        ui::create_canvas(&video_subsystem, "Debug", DEBUG_WIDTH, DEBUG_HEIGHT, options.scale)
+    });
+    let debug_creator = debug_window.as_ref().map(|canvas| canvas.texture_creator());
+    let mut debug_texture = debug_creator.as_ref().map(|creator| ui::create_texture(creator, DEBUG_WIDTH, DEBUG_HEIGHT));
+
+    let mut ui_canvas = ui::create_canvas(&video_subsystem, "Game", X_RES as u32, Y_RES as u32, options.scale);
+    let ui_creator = ui_canvas.texture_creator();
+    let mut ui_texture = ui::create_texture(&ui_creator, X_RES as u32, Y_RES as u32);
+
+    let mut scale_mode = ScaleMode::Integer;
+    ui::set_scale_mode(&mut ui_canvas, scale_mode);
+
+    let mut event_pump = sdl_context.event_pump().unwrap();
+
+    let mut slot = 0;
+
+    // Initialize CPU on separate thread
+    let emulation = std::thread::scope(|scope| {
+        let cpu_thread = scope.spawn(move || emulate(options, &receiver));
+
+        // While the emulator is running
+        while !EMULATOR.read().unwrap().die {
+            delay(1);
+            ui::handle_events(&mut event_pump, &mut ui_canvas, &mut scale_mode, &requests, &mut slot);
+
+            // Only redraw when the PPU has published a new frame
+            FRAMES.latest(|frame| {
+                ui::update_ui_window(&mut ui_canvas, &mut ui_texture, &frame.pixels);
+
+                if let (Some(canvas), Some(texture)) = (debug_window.as_mut(), debug_texture.as_mut()) {
+                    ui::update_debug_window(canvas, texture, &frame.vram);
+                }
+            });
         }
-        y_draw += 4 * SCALE;
-        x_draw = 0;
-    }
 
-    debug_canvas.present();
+        EMULATOR.write().unwrap().running = false;
+        cpu_thread.join()
+    });
+
+    emulation.unwrap();
+}
+
+// Slot N of game.gb is stored next to it as game.ssN
+fn state_path(rom: &str, slot: u8) -> std::path::PathBuf {
+    Path::new(rom).with_extension(format!("ss{slot}"))
 }
 
 pub fn delay(ms: u64) {
@@ -0,0 +1,226 @@
+use std::sync::mpsc::Sender;
+
+use sdl2::{
+    event::Event,
+    keyboard::Keycode,
+    pixels::{Color, PixelFormatEnum},
+    render::{Canvas, Texture, TextureCreator},
+    video::{FullscreenType, Window, WindowContext},
+    EventPump, VideoSubsystem,
+};
+
+use gbemu::comps::{common::PALETTE, emu::EMULATOR, log::{log, Category, Level}, pacing::PACING, ppu::X_RES};
+
+use crate::Request;
+
+// Tile viewer layout: 384 tiles as 16 x 24, with a one pixel gap between tiles
+const DEBUG_TILES_X: u32 = 16;
+const DEBUG_TILES_Y: u32 = 24;
+const DEBUG_TILE_SIZE: u32 = 8 + 1;
+pub const DEBUG_WIDTH: u32 = DEBUG_TILES_X * DEBUG_TILE_SIZE;
+pub const DEBUG_HEIGHT: u32 = DEBUG_TILES_Y * DEBUG_TILE_SIZE;
+
+const DEBUG_BACKGROUND: u32 = 0xFF0B0B0B;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ScaleMode {
+    Aspect,  // Fill the window, keeping the 10:9 aspect ratio
+    Integer, // Largest whole-number multiple that fits in the window
+}
+
+pub fn create_canvas(video_subsystem: &VideoSubsystem, title: &str, width: u32, height: u32, scale: u32) -> Canvas<Window> {
+    let window = video_subsystem
+        .window(title, width * scale, height * scale)
+        .position_centered()
+        .resizable()
+        .build()
+        .unwrap();
+
+    let mut canvas = window.into_canvas().build().unwrap();
+
+    // The renderer scales the logical size to the window and letterboxes the rest
+    canvas.set_logical_size(width, height).unwrap();
+
+    canvas
+}
+
+pub fn create_texture(texture_creator: &TextureCreator<WindowContext>, width: u32, height: u32) -> Texture<'_> {
+    texture_creator
+        .create_texture_streaming(PixelFormatEnum::ABGR8888, width, height)
+        .unwrap()
+}
+
+pub fn set_scale_mode(canvas: &mut Canvas<Window>, mode: ScaleMode) {
+    canvas.set_integer_scale(mode == ScaleMode::Integer).unwrap();
+}
+
+pub fn toggle_fullscreen(canvas: &mut Canvas<Window>) {
+    let window = canvas.window_mut();
+
+    let fullscreen = match window.fullscreen_state() {
+        FullscreenType::Off => FullscreenType::Desktop,
+        _ => FullscreenType::Off,
+    };
+
+    window.set_fullscreen(fullscreen).unwrap();
+}
+
+fn upload(texture: &mut Texture, pixels: &[u32], width: usize) {
+    texture
+        .with_lock(None, |buffer, pitch| {
+            for (y, row) in pixels.chunks(width).enumerate() {
+                let line = &mut buffer[y * pitch..y * pitch + width * 4];
+
+                for (dst, color) in line.chunks_exact_mut(4).zip(row) {
+                    dst.copy_from_slice(&color.to_ne_bytes());
+                }
+            }
+        })
+        .unwrap();
+}
+
+fn present(canvas: &mut Canvas<Window>, texture: &Texture) {
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+    canvas.copy(texture, None, None).unwrap();
+    canvas.present();
+}
+
+pub fn update_ui_window(ui_canvas: &mut Canvas<Window>, ui_texture: &mut Texture, frame_buffer: &[u32]) {
+    upload(ui_texture, frame_buffer, X_RES as usize);
+    present(ui_canvas, ui_texture);
+}
+
+pub fn update_debug_window(debug_canvas: &mut Canvas<Window>, debug_texture: &mut Texture, vram: &[u8]) {
+    let mut pixels = [DEBUG_BACKGROUND; (DEBUG_WIDTH * DEBUG_HEIGHT) as usize];
+    let palette = *PALETTE.read().unwrap();
+
+    // 384 tiles of 16 bytes each, starting at 0x8000
+    for (tile_num, tile) in vram[..0x1800].chunks(16).enumerate() {
+        let tile_x = (tile_num as u32 % DEBUG_TILES_X) * DEBUG_TILE_SIZE;
+        let tile_y = (tile_num as u32 / DEBUG_TILES_X) * DEBUG_TILE_SIZE;
+
+        for (line, bytes) in tile.chunks(2).enumerate() {
+            for bit in 0..8 {
+                let lo = (bytes[0] >> (7 - bit)) & 1;
+                let hi = ((bytes[1] >> (7 - bit)) & 1) << 1;
+
+                let x = tile_x + bit;
+                let y = tile_y + line as u32;
+                pixels[(x + y * DEBUG_WIDTH) as usize] = palette[(hi | lo) as usize];
+            }
+        }
+    }
+
+    upload(debug_texture, &pixels, DEBUG_WIDTH as usize);
+    present(debug_canvas, debug_texture);
+}
+
+pub const STATE_SLOTS: u8 = 10;
+
+pub fn handle_events(
+    event_pump: &mut EventPump,
+    ui_canvas: &mut Canvas<Window>,
+    scale_mode: &mut ScaleMode,
+    requests: &Sender<Request>,
+    slot: &mut u8,
+) {
+    for event in event_pump.poll_iter() {
+        match event {
+            Event::Quit { .. } => EMULATOR.write().unwrap().die = true,
+            Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                ..
+            } => EMULATOR.write().unwrap().die = true,
+            Event::KeyDown {
+                keycode: Some(Keycode::F10),
+                ..
+            } => {
+                *scale_mode = match scale_mode {
+                    ScaleMode::Integer => ScaleMode::Aspect,
+                    ScaleMode::Aspect => ScaleMode::Integer,
+                };
+                set_scale_mode(ui_canvas, *scale_mode);
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::F11),
+                ..
+            } => toggle_fullscreen(ui_canvas),
+            Event::KeyDown {
+                keycode: Some(Keycode::F5),
+                ..
+            } => requests.send(Request::SaveState(*slot)).unwrap(),
+            Event::KeyDown {
+                keycode: Some(Keycode::F9),
+                ..
+            } => requests.send(Request::LoadState(*slot)).unwrap(),
+            Event::KeyDown {
+                keycode: Some(Keycode::Backspace),
+                ..
+            } => {
+                EMULATOR.write().unwrap().rewinding = true;
+                requests.send(Request::Rewind).unwrap();
+            }
+            Event::KeyUp {
+                keycode: Some(Keycode::Backspace),
+                ..
+            } => EMULATOR.write().unwrap().rewinding = false,
+            Event::KeyDown {
+                keycode: Some(Keycode::P),
+                repeat: false,
+                ..
+            } => {
+                let mut emu = EMULATOR.write().unwrap();
+                emu.paused = !emu.paused;
+                log(Category::Timing, Level::Info, format_args!("{}", if emu.paused { "Paused" } else { "Resumed" }));
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::N),
+                ..
+            } => {
+                EMULATOR.write().unwrap().paused = true;
+                PACING.write().unwrap().advance = true;
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::Tab),
+                repeat: false,
+                ..
+            } => PACING.write().unwrap().turbo = true,
+            Event::KeyUp {
+                keycode: Some(Keycode::Tab),
+                ..
+            } => PACING.write().unwrap().turbo = false,
+            Event::KeyDown {
+                keycode: Some(Keycode::Minus),
+                ..
+            } => {
+                let mut pacing = PACING.write().unwrap();
+                pacing.slower();
+                log(Category::Timing, Level::Info, format_args!("Speed: {}x", pacing.speed));
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::Equals),
+                ..
+            } => {
+                let mut pacing = PACING.write().unwrap();
+                pacing.faster();
+                log(Category::Timing, Level::Info, format_args!("Speed: {}x", pacing.speed));
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::F6),
+                ..
+            } => {
+                *slot = (*slot + STATE_SLOTS - 1) % STATE_SLOTS;
+                log(Category::UI, Level::Info, format_args!("Save state slot {slot}"));
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::F7),
+                ..
+            } => {
+                *slot = (*slot + 1) % STATE_SLOTS;
+                log(Category::UI, Level::Info, format_args!("Save state slot {slot}"));
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,114 @@
+// Runs Blargg's test ROMs headless and checks the result they print over serial
+
+use std::{
+    fs,
+    path::PathBuf,
+    process::{Command, Stdio},
+    thread::sleep,
+    time::Duration,
+};
+
+// Runs rom until it prints Passed or Failed, or until it has used up its budget of frames (70224 cycles each)
+fn run_blargg(rom: &str, frames: u32) {
+    let name = rom.trim_end_matches(".gb");
+    let serial_out = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{name}.serial"));
+    let _ = fs::remove_file(&serial_out);
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_gbemu"))
+        .args(["--headless", "--speed", "0", "--frames", &frames.to_string(), "--serial-out"])
+        .arg(&serial_out)
+        .arg(format!("roms/{rom}"))
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    loop {
+        // The emulator exits on its own once the budget is used up, so check the status before reading the
+        // serial output, to not miss a result printed right before exiting
+        let status = child.try_wait().unwrap();
+        let serial = fs::read_to_string(&serial_out).unwrap_or_default();
+
+        if serial.contains("Passed") || serial.contains("Failed") || status.is_some() {
+            let _ = child.kill();
+            let output = child.wait_with_output().unwrap();
+
+            assert!(serial.contains("Passed"), "{rom} didn't pass:\n{serial}{}", match status {
+                Some(status) if !status.success() => format!("\nThe emulator exited with {status}:\n{}", String::from_utf8_lossy(&output.stderr)),
+                Some(_) => format!("\nNo result after {frames} frames"),
+                None => String::new(),
+            });
+
+            return;
+        }
+
+        sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn special() {
+    run_blargg("01-special.gb", 600);
+}
+
+#[test]
+fn interrupts() {
+    run_blargg("02-interrupts.gb", 600);
+}
+
+#[test]
+fn op_sp_hl() {
+    run_blargg("03-op-sp,hl.gb", 600);
+}
+
+#[test]
+fn op_r_imm() {
+    run_blargg("04-op-r,imm.gb", 600);
+}
+
+#[test]
+fn op_rp() {
+    run_blargg("05-op-rp.gb", 600);
+}
+
+#[test]
+fn ld_r_r() {
+    run_blargg("06-ld-r,r.gb", 600);
+}
+
+#[test]
+fn jr_jp_call_ret_rst() {
+    run_blargg("07-jr,jp,call,ret,rst.gb", 600);
+}
+
+#[test]
+fn misc_instrs() {
+    run_blargg("08-misc-instrs.gb", 600);
+}
+
+#[test]
+fn op_r_r() {
+    run_blargg("09-op-r,r.gb", 1500);
+}
+
+#[test]
+fn bit_ops() {
+    run_blargg("10-bit-ops.gb", 2000);
+}
+
+#[test]
+fn op_a_hl() {
+    run_blargg("11-op-a,hl.gb", 2500);
+}
+
+#[test]
+#[ignore = "MBC1 cartridge, there is no mapper yet"]
+fn cpu_instrs() {
+    run_blargg("cpu_instrs.gb", 6000);
+}
+
+#[test]
+#[ignore = "MBC1 cartridge, there is no mapper yet"]
+fn mem_timing() {
+    run_blargg("mem_timing.gb", 600);
+}
@@ -0,0 +1,32 @@
+// Helpers shared by the tests, not every test uses all of them
+#![allow(dead_code)]
+
+use std::net::TcpListener;
+
+// A ROM only cartridge that jumps from the entry point to code at 0x150
+pub fn rom(code: &[u8]) -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]); // NOP, JP $0150
+    rom[0x134..0x138].copy_from_slice(b"TEST");
+    rom[0x14B] = 0x01;
+    rom[0x14D] = rom[0x134..=0x14C].iter().fold(0u8, |sum, byte| sum.wrapping_sub(*byte).wrapping_sub(1));
+    rom[0x150..0x150 + code.len()].copy_from_slice(code);
+
+    rom
+}
+
+// Loads B, C, D, E, H and L, then LD B,B and loops forever, the way Mooneye tests end
+pub fn result(values: [u8; 6]) -> Vec<u8> {
+    let mut code = Vec::new();
+    for (opcode, value) in [0x06, 0x0E, 0x16, 0x1E, 0x26, 0x2E].iter().zip(values) {
+        code.extend([*opcode, value]);
+    }
+    code.extend([0x40, 0x18, 0xFE]); // LD B,B, JR -2
+
+    code
+}
+
+// NOTICE: The port could be taken again before the emulator listens on it
+pub fn free_port() -> u16 {
+    TcpListener::bind(("127.0.0.1", 0)).unwrap().local_addr().unwrap().port()
+}
@@ -0,0 +1,136 @@
+// Drives the --gdb stub of the gbemu binary with a scripted remote serial protocol client
+
+mod common;
+
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    process::{Child, Command, Stdio},
+    thread::sleep,
+    time::Duration,
+};
+
+use common::free_port;
+
+const ROM: &str = "roms/06-ld-r,r.gb";
+
+struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    fn connect(port: u16) -> Client {
+        for _ in 0..100 {
+            if let Ok(stream) = TcpStream::connect(("127.0.0.1", port)) {
+                stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+                return Client { stream };
+            }
+
+            sleep(Duration::from_millis(50));
+        }
+
+        panic!("Couldn't connect to the GDB stub on port {port}");
+    }
+
+    fn byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.stream.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+
+    fn send(&mut self, data: &str) {
+        let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(self.stream, "${data}#{checksum:02x}").unwrap();
+        assert_eq!(self.byte(), b'+', "{data} wasn't acknowledged");
+    }
+
+    // Sends a packet and returns the reply, acknowledging both ways
+    fn request(&mut self, data: &str) -> String {
+        self.send(data);
+        self.reply()
+    }
+
+    fn reply(&mut self) -> String {
+        while self.byte() != b'$' {}
+
+        let mut reply = Vec::new();
+        loop {
+            match self.byte() {
+                b'#' => break,
+                b'}' => {
+                    let byte = self.byte();
+                    reply.push(byte ^ 0x20);
+                }
+                byte => reply.push(byte),
+            }
+        }
+
+        let checksum = [self.byte(), self.byte()];
+        let expected = u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).unwrap();
+        assert_eq!(reply.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)), expected, "Bad checksum");
+
+        self.stream.write_all(b"+").unwrap();
+        String::from_utf8(reply).unwrap()
+    }
+
+    fn pc(&mut self) -> u16 {
+        let reply = self.request("p5");
+        u16::from_str_radix(&format!("{}{}", &reply[2..4], &reply[0..2]), 16).unwrap()
+    }
+}
+
+fn start(port: u16) -> Child {
+    Command::new(env!("CARGO_BIN_EXE_gbemu"))
+        .args(["--headless", "--speed", "0", "--gdb", &port.to_string(), ROM])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap()
+}
+
+#[test]
+fn gdb_stub() {
+    let port = free_port();
+    let mut emulator = start(port);
+    let mut client = Client::connect(port);
+
+    assert!(client.request("qSupported:swbreak+").starts_with("PacketSize="));
+    assert_eq!(client.request("?"), "S05");
+
+    // Post-boot DMG registers: AF=01B0 BC=0013 DE=00D8 HL=014D SP=FFFE PC=0100
+    assert_eq!(client.request("g"), "b0011300d8004d01feff0001");
+    assert_eq!(client.request("m100,4"), "00c31302");
+
+    // Software breakpoint on the JP target
+    assert_eq!(client.request("Z0,213,1"), "OK");
+    assert_eq!(client.request("c"), "S05");
+    assert_eq!(client.pc(), 0x0213);
+    assert_eq!(client.request("z0,213,1"), "OK");
+
+    // LD HL,$4000
+    assert_eq!(client.request("s"), "S05");
+    assert_eq!(client.pc(), 0x0216);
+    assert_eq!(client.request("p3"), "0040");
+
+    // Registers and memory can be written
+    assert_eq!(client.request("P1=3412"), "OK");
+    assert_eq!(client.request("p1"), "3412");
+    assert_eq!(client.request("Mc100,2:abcd"), "OK");
+    assert_eq!(client.request("mc100,2"), "abcd");
+
+    // The test ROM copies its code to WRAM starting at $C000
+    assert_eq!(client.request("Z2,c000,1"), "OK");
+    assert_eq!(client.request("c"), "S05");
+    assert_eq!(client.request("mc000,1"), "c3");
+    assert_eq!(client.request("z2,c000,1"), "OK");
+
+    // Interrupt the running core with Ctrl-C
+    client.send("c");
+    sleep(Duration::from_millis(100));
+    client.stream.write_all(&[0x03]).unwrap();
+    assert_eq!(client.reply(), "S02");
+
+    client.send("k");
+    assert!(emulator.wait().unwrap().success());
+}
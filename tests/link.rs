@@ -0,0 +1,97 @@
+// Connects two gbemu processes with a link cable over loopback and has them exchange bytes
+
+mod common;
+
+use std::{
+    fs,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use common::{free_port, result, rom};
+
+const BYTES: u8 = 16;
+
+// Operand of a JR at code[at] that jumps to code[to]
+fn jr(at: usize, to: usize) -> u8 {
+    (to as isize - (at as isize + 2)) as u8
+}
+
+// Sends the bytes 0 to BYTES - 1, the side driving the clock expects their complement back and the other side the
+// bytes themselves. Ends like a Mooneye test, with the Fibonacci numbers in B - L if every byte matched.
+// A side that isn't waiting yet answers with whatever is in SB, so the side driving the clock sends a byte again
+// until the complement comes back
+fn link_rom(drives_clock: bool) -> Vec<u8> {
+    let mut code = vec![0x0E, 0x00]; // LD C,0
+
+    let next = code.len();
+    code.push(0x79); // LD A,C
+    if !drives_clock {
+        code.push(0x2F); // CPL
+    }
+    code.extend([0xE0, 0x01]); // LDH (SB),A
+    code.extend([0x3E, if drives_clock { 0x81 } else { 0x80 }, 0xE0, 0x02]); // LD A,$81 / $80, LDH (SC),A
+
+    let wait = code.len();
+    code.extend([0xF0, 0x02, 0xCB, 0x7F]); // LDH A,(SC), BIT 7,A
+    code.extend([0x20, jr(code.len(), wait)]); // JR NZ,wait
+
+    if drives_clock {
+        code.extend([0x79, 0x2F, 0x47, 0xF0, 0x01, 0xB8]); // LD A,C, CPL, LD B,A, LDH A,(SB), CP B
+    } else {
+        code.extend([0xF0, 0x01, 0xB9]); // LDH A,(SB), CP C
+    }
+
+    let fail_jump = code.len();
+    if drives_clock {
+        code.extend([0x20, jr(code.len(), next)]); // JR NZ,next
+    } else {
+        code.extend([0x20, 0x00]); // JR NZ,fail
+    }
+    code.extend([0x0C, 0x79, 0xFE, BYTES]); // INC C, LD A,C, CP BYTES
+    code.extend([0x20, jr(code.len(), next)]); // JR NZ,next
+
+    code.extend(result([3, 5, 8, 13, 21, 34]));
+    if !drives_clock {
+        code[fail_jump + 1] = jr(fail_jump, code.len());
+        code.extend(result([0x42; 6]));
+    }
+
+    rom(&code)
+}
+
+#[test]
+fn exchange_over_loopback() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("link");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("clock.gb"), link_rom(true)).unwrap();
+    fs::write(dir.join("follow.gb"), link_rom(false)).unwrap();
+
+    let port = free_port();
+
+    let run = |rom: &str, link: [String; 2]| {
+        Command::new(env!("CARGO_BIN_EXE_gbemu"))
+            .args(["--headless", "--speed", "0", "--mooneye", "--frames", "120"])
+            .args(link)
+            .arg(dir.join(rom))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap()
+    };
+
+    let listener = run("clock.gb", [String::from("--link-listen"), port.to_string()]);
+    let connector = run("follow.gb", [String::from("--link-connect"), format!("127.0.0.1:{port}")]);
+
+    for (name, child) in [("clock.gb", listener), ("follow.gb", connector)] {
+        let output = child.wait_with_output().unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(
+            output.status.success() && stdout.contains("Mooneye test passed"),
+            "{name} exited with {}:\n{stdout}{}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr),
+        );
+    }
+}
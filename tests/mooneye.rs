@@ -0,0 +1,37 @@
+// Runs gbemu-mooneye on small generated ROMs that end the way Mooneye tests do
+
+mod common;
+
+use std::{fs, path::PathBuf, process::Command};
+
+use common::{result, rom};
+
+#[test]
+fn pass_fail_table() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("mooneye");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("timer")).unwrap();
+
+    fs::write(dir.join("pass.gb"), rom(&result([3, 5, 8, 13, 21, 34]))).unwrap();
+    fs::write(dir.join("fail.gb"), rom(&result([0x42; 6]))).unwrap();
+    fs::write(dir.join("timer/hang.gb"), rom(&[0x18, 0xFE])).unwrap();
+    fs::write(dir.join("notes.txt"), "Not a ROM").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_gbemu-mooneye"))
+        .arg(&dir)
+        .args(["--frames", "10"])
+        .output()
+        .unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines = stdout.lines().map(|line| line.split_whitespace().collect::<Vec<&str>>().join(" ")).collect::<Vec<String>>();
+
+    assert_eq!(lines, [
+        "fail.gb FAIL B: 42 C: 42 D: 42 E: 42 H: 42 L: 42",
+        "pass.gb PASS",
+        "timer/hang.gb TIMEOUT",
+        "",
+        "1/3 passed",
+    ], "{stdout}");
+    assert_eq!(output.status.code(), Some(1));
+}
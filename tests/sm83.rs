@@ -0,0 +1,342 @@
+// Runs SM83 single-step test vectors (github.com/SingleStepTests/sm83) against CPUContext::step on a FlatBus
+//
+// Each file holds a JSON array of cases like
+//   { "name": "80 0000",
+//     "initial": { "pc": 256, "sp": 65534, "a": 58, "b": 198, ..., "ime": 0, "ie": 0, "ram": [[256, 128]] },
+//     "final": { ... },
+//     "cycles": [[256, 128, "r-m"], [null, null, "---"]] }
+// with one cycles entry per M-cycle: "r-m" is a read, "-wm" a write and anything else (or null) an idle cycle.
+//
+// tests/sm83/sample.json is run by default, the full suite with
+//   SM83_TESTS=path/to/sm83/v1 cargo test --test sm83 -- --ignored
+
+use std::{any::Any, collections::BTreeMap, fs, panic::{self, AssertUnwindSafe}, path::Path};
+
+use gbemu::comps::{bus::FlatBus, cpu::CPUContext};
+
+const SHOWN_FAILURES: usize = 5; // Per file
+
+// Files of the full suite that aren't run: STOP needs more than the CPU, the illegal opcodes lock it up
+const SKIPPED: [&str; 12] = ["10", "d3", "db", "dd", "e3", "e4", "eb", "ec", "ed", "f4", "fc", "fd"];
+
+enum Json {
+    Null,
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(BTreeMap<String, Json>),
+}
+
+impl Json {
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.get(key),
+            _ => None,
+        }
+    }
+
+    fn number(&self) -> Option<u16> {
+        match self {
+            Json::Number(number) => Some(*number as u16),
+            _ => None,
+        }
+    }
+
+    fn array(&self) -> &[Json] {
+        match self {
+            Json::Array(items) => items,
+            _ => &[],
+        }
+    }
+}
+
+struct JsonParser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+// Just enough JSON for the test vectors, strings have no escapes
+impl JsonParser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.text.get(self.pos).is_some_and(|c| c.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.text.get(self.pos) != Some(&c) {
+            return Err(format!("Expected {} at offset {}", c as char, self.pos));
+        }
+
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        self.skip_whitespace();
+        let matches = self.text.get(self.pos) == Some(&c);
+        self.pos += matches as usize;
+        matches
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+
+        match self.text.get(self.pos) {
+            Some(b'{') => {
+                self.pos += 1;
+                let mut fields = BTreeMap::new();
+
+                if !self.eat(b'}') {
+                    loop {
+                        let Json::String(key) = self.value()? else {
+                            return Err(format!("Expected a key at offset {}", self.pos));
+                        };
+                        self.expect(b':')?;
+                        fields.insert(key, self.value()?);
+
+                        if !self.eat(b',') {
+                            self.expect(b'}')?;
+                            break;
+                        }
+                    }
+                }
+
+                Ok(Json::Object(fields))
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+
+                if !self.eat(b']') {
+                    loop {
+                        items.push(self.value()?);
+
+                        if !self.eat(b',') {
+                            self.expect(b']')?;
+                            break;
+                        }
+                    }
+                }
+
+                Ok(Json::Array(items))
+            }
+            Some(b'"') => {
+                let start = self.pos + 1;
+                let len = self.text[start..].iter().position(|c| *c == b'"').ok_or("Unterminated string")?;
+                self.pos = start + len + 1;
+
+                Ok(Json::String(String::from_utf8_lossy(&self.text[start..start + len]).into_owned()))
+            }
+            _ => {
+                let start = self.pos;
+                while self.text.get(self.pos).is_some_and(|c| c.is_ascii_alphanumeric() || b"+-.".contains(c)) {
+                    self.pos += 1;
+                }
+
+                match std::str::from_utf8(&self.text[start..self.pos]).unwrap() {
+                    "null" => Ok(Json::Null),
+                    // Read as numbers, so "ime" works either way
+                    "true" => Ok(Json::Number(1.0)),
+                    "false" => Ok(Json::Number(0.0)),
+                    word => word.parse().map(Json::Number).map_err(|_| format!("Unexpected {word:?} at offset {start}")),
+                }
+            }
+        }
+    }
+}
+
+fn parse_json(text: &str) -> Result<Json, String> {
+    JsonParser { text: text.as_bytes(), pos: 0 }.value()
+}
+
+fn field(state: &Json, key: &str) -> u16 {
+    state.get(key).and_then(Json::number).unwrap_or_else(|| panic!("Missing {key}"))
+}
+
+fn set_state(cpu: &mut CPUContext, bus: &mut FlatBus, state: &Json) {
+    let regs = &mut cpu.registers;
+    regs.a = field(state, "a") as u8;
+    regs.f = field(state, "f") as u8;
+    regs.b = field(state, "b") as u8;
+    regs.c = field(state, "c") as u8;
+    regs.d = field(state, "d") as u8;
+    regs.e = field(state, "e") as u8;
+    regs.h = field(state, "h") as u8;
+    regs.l = field(state, "l") as u8;
+    regs.pc = field(state, "pc");
+    regs.sp = field(state, "sp");
+
+    cpu.int_master_enabled = field(state, "ime") != 0;
+    cpu.ie_register = state.get("ie").and_then(Json::number).unwrap_or(0) as u8;
+    cpu.int_flags = 0;
+    cpu.enabling_ime = false;
+    cpu.halted = false;
+
+    bus.memory.fill(0);
+    for entry in state.get("ram").map(Json::array).unwrap_or(&[]) {
+        let entry = entry.array();
+        bus.memory[entry[0].number().unwrap() as usize] = entry[1].number().unwrap() as u8;
+    }
+    bus.log.clear();
+    bus.cycles = 0;
+}
+
+// Runs a single case, returns what didn't match
+fn run_case(cpu: &mut CPUContext, bus: &mut FlatBus, case: &Json) -> Vec<String> {
+    let initial = case.get("initial").unwrap();
+    let expected = case.get("final").unwrap();
+    set_state(cpu, bus, initial);
+    cpu.step(bus);
+
+    let mut errors = Vec::new();
+    let regs = &cpu.registers;
+    let registers = [
+        ("a", regs.a as u16), ("f", regs.f as u16), ("b", regs.b as u16), ("c", regs.c as u16),
+        ("d", regs.d as u16), ("e", regs.e as u16), ("h", regs.h as u16), ("l", regs.l as u16),
+        ("pc", regs.pc), ("sp", regs.sp), ("ime", cpu.int_master_enabled as u16),
+    ];
+
+    for (name, actual) in registers {
+        let wanted = field(expected, name);
+        if actual != wanted {
+            errors.push(format!("{name}: expected {wanted:02X}, got {actual:02X}"));
+        }
+    }
+
+    for entry in expected.get("ram").map(Json::array).unwrap_or(&[]) {
+        let entry = entry.array();
+        let address = entry[0].number().unwrap();
+        let wanted = entry[1].number().unwrap() as u8;
+        let actual = bus.memory[address as usize];
+
+        if actual != wanted {
+            errors.push(format!("[{address:04X}]: expected {wanted:02X}, got {actual:02X}"));
+        }
+    }
+
+    let expected_cycles = case.get("cycles").map(Json::array).unwrap_or(&[]);
+    if bus.cycles as usize != expected_cycles.len() {
+        errors.push(format!("expected {} M-cycles, took {}", expected_cycles.len(), bus.cycles));
+    }
+
+    // What happened on the bus in each M-cycle, idle ones included
+    let wanted_cycles = expected_cycles
+        .iter()
+        .map(|cycle| {
+            let cycle = cycle.array();
+            let pins = match cycle.get(2) {
+                Some(Json::String(pins)) => pins.as_str(),
+                _ => "---",
+            };
+            let number = |i: usize| cycle.get(i).and_then(Json::number).unwrap_or(0);
+            let (address, value) = (number(0), number(1));
+
+            match pins.as_bytes() {
+                [b'r', ..] => format!("read {address:04X} = {value:02X}"),
+                [_, b'w', ..] => format!("write {address:04X} = {value:02X}"),
+                _ => String::from("idle"),
+            }
+        })
+        .collect::<Vec<String>>();
+
+    let mut cycles = vec![String::from("idle"); bus.cycles as usize];
+    for access in &bus.log {
+        let cycle = access.cycle as usize;
+        if cycles.len() <= cycle {
+            cycles.resize(cycle + 1, String::from("idle"));
+        }
+
+        if cycles[cycle] != "idle" {
+            errors.push(format!("M-cycle {cycle}: more than one bus access"));
+        }
+
+        let kind = if access.write { "write" } else { "read" };
+        cycles[cycle] = format!("{kind} {:04X} = {:02X}", access.address, access.value);
+    }
+
+    for i in 0..wanted_cycles.len().max(cycles.len()) {
+        let wanted = wanted_cycles.get(i).map(String::as_str).unwrap_or("nothing");
+        let actual = cycles.get(i).map(String::as_str).unwrap_or("nothing");
+
+        if wanted != actual {
+            errors.push(format!("M-cycle {i}: expected {wanted}, got {actual}"));
+        }
+    }
+
+    errors
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+        (Some(message), _) => message,
+        (_, Some(message)) => message,
+        _ => "?",
+    }
+}
+
+// Runs every case in path, returns the number of cases and a report of the failures
+fn run_file(path: &Path) -> (usize, Vec<String>) {
+    let text = fs::read_to_string(path).unwrap_or_else(|e| panic!("Couldn't read {}: {e}", path.display()));
+    let cases = parse_json(&text).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+    let cases = cases.array();
+
+    let mut cpu = CPUContext::new();
+    let mut bus = FlatBus::default();
+    let mut failures = Vec::new();
+
+    for case in cases {
+        // A panic only fails its own case, so the rest of the file is still checked
+        let errors = panic::catch_unwind(AssertUnwindSafe(|| run_case(&mut cpu, &mut bus, case)))
+            .unwrap_or_else(|payload| vec![format!("panicked: {}", panic_message(&*payload))]);
+        if !errors.is_empty() {
+            let name = match case.get("name") {
+                Some(Json::String(name)) => name.as_str(),
+                _ => "?",
+            };
+            failures.push(format!("{name}:\n    {}", errors.join("\n    ")));
+        }
+    }
+
+    (cases.len(), failures)
+}
+
+fn run_files(paths: &[&Path]) {
+    let mut report = Vec::new();
+    let mut total_failures = 0;
+
+    for path in paths {
+        let (count, failures) = run_file(path);
+        let name = path.file_name().unwrap().to_string_lossy();
+
+        if !failures.is_empty() {
+            report.push(format!("{name}: {} of {count} failed", failures.len()));
+            report.extend(failures.iter().take(SHOWN_FAILURES).cloned());
+        }
+
+        total_failures += failures.len();
+    }
+
+    assert!(total_failures == 0, "{total_failures} cases failed\n{}", report.join("\n"));
+}
+
+#[test]
+fn sample() {
+    run_files(&[Path::new("tests/sm83/sample.json")]);
+}
+
+#[test]
+#[ignore = "needs the SM83 test vectors, set SM83_TESTS to their directory"]
+fn single_step_tests() {
+    let dir = std::env::var("SM83_TESTS").unwrap_or(String::from("tests/sm83/v1"));
+    let mut paths = fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("Couldn't read {dir}: {e}"))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+        .filter(|path| !SKIPPED.contains(&path.file_stem().unwrap().to_string_lossy().to_lowercase().as_str()))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    run_files(&paths.iter().map(|path| path.as_path()).collect::<Vec<&Path>>());
+}